@@ -4,28 +4,42 @@ extern crate criterion;
 use criterion::black_box;
 use criterion::Criterion;
 
-use advent_of_rust_2019::intcode_computer::Computer;
+use advent_of_rust_2019::intcode_computer::{Backend, Computer};
 use advent_of_rust_2019::{load_file, parse_custom_separated};
 
 fn bench_sum_of_primes(c: &mut Criterion) {
-    c.bench_function("Sum Of Primes", |b| {
-        let file = load_file("sum-of-primes.in");
-        let program: Vec<_> = parse_custom_separated::<isize>(&file, ",").collect();
-
-        b.iter(move || {
-            let mut computer = black_box(Computer::with_input(program.clone(), || Some(100000)));
-
-            computer.run_until_halt_or_paused(true);
-
-            let output = black_box(computer.last_output());
-
-            assert!(
-                output == Some(454396537),
-                "Expected 454396537 got {:?}",
-                output
-            );
+    let file = load_file("sum-of-primes.in");
+    let program: Vec<_> = parse_custom_separated::<isize>(&file, ",").collect();
+    let mut group = c.benchmark_group("Sum Of Primes");
+
+    for &(name, backend, cache) in &[
+        ("cached", Backend::Interpreter, true),
+        ("uncached", Backend::Interpreter, false),
+        ("compiled", Backend::Compiled, true),
+    ] {
+        let program = program.clone();
+
+        group.bench_function(name, move |b| {
+            b.iter(|| {
+                let mut computer =
+                    black_box(Computer::with_input(program.clone(), || Some(100000)));
+                computer.set_backend(backend);
+                computer.set_instruction_cache(cache);
+
+                computer.run_until_halt_or_paused(true);
+
+                let output = black_box(computer.last_output());
+
+                assert!(
+                    output == Some(454396537),
+                    "Expected 454396537 got {:?}",
+                    output
+                );
+            });
         });
-    });
+    }
+
+    group.finish();
 }
 
 criterion_group!(benches, bench_sum_of_primes);
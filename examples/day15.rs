@@ -1,4 +1,3 @@
-use std::cell::RefCell;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::io::Write;
@@ -11,7 +10,7 @@ use ansi_term::{ANSIByteString, ANSIByteStrings, Style};
 use thread_priority::*;
 
 use advent_of_rust_2019::day15::{Direction, Location, Status, Tile, World};
-use advent_of_rust_2019::intcode_computer::Computer;
+use advent_of_rust_2019::intcode_computer::{Computer, ExecutionState};
 use advent_of_rust_2019::{load_file, parse_custom_separated};
 
 const RENDER_TICK_RATE: Duration = Duration::from_millis(16);
@@ -176,18 +175,20 @@ fn explore_world(
     use_synchronized_output: bool,
 ) -> (World, Vec<Vec<Sprite>>, mpsc::Sender<Diff>) {
     let program: Vec<isize> = parse_custom_separated(input, ",").collect();
-    let next_input: RefCell<Direction> = RefCell::new(Direction::North);
+    let mut direction = Direction::North;
     let mut world = World::new();
     let mut computer = Computer::new(program);
-    computer.set_input(|| Some((*next_input.borrow()) as isize));
     let mut last_sprite_map: Vec<Vec<Sprite>> = vec![];
 
     loop {
-        computer.run_until_halt_or_paused(true);
-        let status = Status::try_from(computer.last_output().unwrap())
-            .expect("Expected to be able to parse status");
+        computer.push_input(direction as isize);
+        let status = match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(value) => {
+                Status::try_from(value).expect("Expected to be able to parse status")
+            }
+            state => panic!("Unexpected execution state {:?}", state),
+        };
 
-        let direction = *next_input.borrow();
         world.update(direction, status);
 
         let sprites = world_to_sprite_map(&world, world.done_exploring(), None, None);
@@ -200,7 +201,7 @@ fn explore_world(
             break;
         }
 
-        *next_input.borrow_mut() = world.next_direction();
+        direction = world.next_direction();
     }
 
     (world, last_sprite_map, tx)
@@ -0,0 +1,28 @@
+use std::env;
+use std::process;
+
+use advent_of_rust_2019::intcode::asm::assemble;
+use advent_of_rust_2019::load_file;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: asm <source>");
+            process::exit(2);
+        }
+    };
+
+    let source = load_file(&path);
+
+    match assemble(&source) {
+        Ok(program) => {
+            let words: Vec<_> = program.iter().map(ToString::to_string).collect();
+            println!("{}", words.join(","));
+        }
+        Err(error) => {
+            eprintln!("{}:{}", path, error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::env;
+use std::process;
+
+use advent_of_rust_2019::intcode::disasm::disassemble;
+use advent_of_rust_2019::intcode::program::Program;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: disasm <program>");
+            process::exit(2);
+        }
+    };
+
+    let program = Program::from_file(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    print!("{}", disassemble(&program.words()));
+}
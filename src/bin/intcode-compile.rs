@@ -0,0 +1,22 @@
+use std::env;
+use std::process;
+
+use advent_of_rust_2019::intcode::compile::emit_rust;
+use advent_of_rust_2019::intcode::program::Program;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-compile <program>");
+            process::exit(2);
+        }
+    };
+
+    let program = Program::from_file(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+
+    print!("{}", emit_rust(&program.words()));
+}
@@ -0,0 +1,46 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use advent_of_rust_2019::intcode::debugger::{Action, Debugger};
+use advent_of_rust_2019::intcode::program::Program;
+use advent_of_rust_2019::intcode_computer::Computer;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: intcode-dbg <program>");
+            process::exit(2);
+        }
+    };
+
+    let program = Program::from_file(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+    let mut debugger = Debugger::new(Computer::new(program.words()));
+
+    println!("{}", debugger.current());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let line = match lines.next() {
+            Some(line) => line.expect("Failed to read from stdin"),
+            None => break,
+        };
+
+        let mut out = String::new();
+        let action = debugger.execute(&line, &mut out);
+        print!("{}", out);
+
+        if action == Action::Quit {
+            break;
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use advent_of_rust_2019::intcode::fuzz::{fuzz, variants};
+
+const USAGE: &str = "\
+Usage: intcode-fuzz [--seed <seed>] [--iterations <count>] [--out <dir>]
+
+Checks generated programs until a variant disagrees with the reference, then writes the minimized
+program to <dir>, `fuzz` by default, and exits with 1.";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn number(args: &mut impl Iterator<Item = String>, flag: &str) -> u64 {
+    let value = args
+        .next()
+        .unwrap_or_else(|| usage_error(&format!("Expected a number after `{}`", flag)));
+
+    value
+        .parse()
+        .unwrap_or_else(|_| usage_error(&format!("Invalid number `{}`", value)))
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut seed = None;
+    let mut iterations = 10_000;
+    let mut out = PathBuf::from("fuzz");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => seed = Some(number(&mut args, "--seed")),
+            "--iterations" => iterations = number(&mut args, "--iterations"),
+            "--out" => {
+                out = args
+                    .next()
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| usage_error("Expected a directory after `--out`"))
+            }
+            _ => usage_error(&format!("Unexpected argument `{}`", arg)),
+        }
+    }
+
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)
+    });
+    eprintln!("Checking {} programs from seed {}", iterations, seed);
+
+    let failure = match fuzz(seed, iterations, &variants()) {
+        Ok(()) => return,
+        Err(failure) => failure,
+    };
+
+    let path = out.join(format!("{}-{}.txt", failure.seed, failure.iteration));
+    let written = fs::create_dir_all(&out).and_then(|_| fs::write(&path, failure.case.to_string()));
+    if let Err(error) = written {
+        eprintln!("Failed to write {}: {}", path.display(), error);
+    }
+
+    eprintln!("{}", failure.mismatch);
+    eprintln!("Wrote the minimized program to {}", path.display());
+    process::exit(1);
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+
+use advent_of_rust_2019::intcode::program::Program;
+use advent_of_rust_2019::intcode::trace::{Profile, TraceWriter};
+use advent_of_rust_2019::intcode_computer::{Computer, ExecutionState};
+
+const USAGE: &str = "Usage: intcode-profile [--trace <file>] <program> [input ...]";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut trace_path = None;
+    let mut path = None;
+    let mut inputs = vec![];
+
+    while let Some(arg) = args.next() {
+        if arg == "--trace" {
+            trace_path = args.next();
+        } else if path.is_none() {
+            path = Some(arg);
+        } else {
+            match arg.parse::<isize>() {
+                Ok(input) => inputs.push(input),
+                Err(_) => {
+                    eprintln!("Invalid input `{}`\n{}", arg, USAGE);
+                    process::exit(2);
+                }
+            }
+        }
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    let program = Program::from_file(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(1);
+    });
+    let mut computer = Computer::new(program.words());
+    computer.push_inputs(inputs);
+
+    let trace = trace_path.map(|trace_path| {
+        let file = File::create(&trace_path).unwrap_or_else(|error| {
+            eprintln!("Failed to create {}: {}", trace_path, error);
+            process::exit(1);
+        });
+
+        TraceWriter::new(BufWriter::new(file))
+    });
+
+    match trace {
+        Some(trace) => computer.set_tracer((Profile::new(), trace)),
+        None => computer.set_tracer(Profile::new()),
+    }
+
+    let result = computer.try_run(false);
+    let outputs: Vec<_> = computer.drain_outputs().map(|v| v.to_string()).collect();
+    println!("Outputs: {}", outputs.join(","));
+
+    match result {
+        Ok(ExecutionState::NeedsInput) => eprintln!("Stopped waiting for input"),
+        Ok(_) => (),
+        Err(error) => eprintln!("{}", error),
+    }
+
+    let profile = match computer.take_tracer::<(Profile, TraceWriter<BufWriter<File>>)>() {
+        Some((profile, trace)) => {
+            if let Err(error) = trace.finish() {
+                eprintln!("Failed to write trace: {}", error);
+                process::exit(1);
+            }
+
+            profile
+        }
+        None => computer
+            .take_tracer::<Profile>()
+            .expect("The profile should still be attached"),
+    };
+
+    print!("\n{}", profile);
+}
@@ -0,0 +1,187 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use advent_of_rust_2019::intcode::ascii::AsciiComputer;
+use advent_of_rust_2019::intcode::parse::ParseError;
+use advent_of_rust_2019::intcode::patch::PatchSet;
+use advent_of_rust_2019::intcode::program::Program;
+use advent_of_rust_2019::intcode_computer::{Computer, ExecutionState};
+
+const USAGE: &str = "\
+Usage: intcode-run [--ascii] [--patch <addr>=<value> ...] [--patches <file> ...]
+                   [--input <v>,<v>,...] <program>
+
+Exit codes: 0 when the program halts, 1 when it faults, 2 for usage errors and 3 when it needs
+input after stdin has ended.";
+
+const EXIT_FAULT: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_STARVED: i32 = 3;
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+/// The next line of stdin without its line ending, `None` at the end of input.
+fn read_line(stdin: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+
+    match stdin.read_line(&mut line) {
+        Ok(0) => None,
+        Ok(_) => Some(line.trim_end_matches(&['\r', '\n'][..]).to_string()),
+        Err(error) => {
+            eprintln!("Failed to read stdin: {}", error);
+            process::exit(EXIT_FAULT);
+        }
+    }
+}
+
+/// Print every output and prompt for every input, one number per line.
+fn run_numeric(mut computer: Computer) -> i32 {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+
+    loop {
+        match computer.try_run(true) {
+            Ok(ExecutionState::Halted) => return 0,
+            Ok(ExecutionState::NeedsInput) => loop {
+                eprint!("input> ");
+                let _ = io::stderr().flush();
+
+                let line = match read_line(&mut stdin) {
+                    Some(line) => line,
+                    None => return EXIT_STARVED,
+                };
+
+                match line.trim().parse() {
+                    Ok(value) => {
+                        computer.push_input(value);
+                        break;
+                    }
+                    Err(_) => eprintln!("Invalid input `{}`, expected a number", line.trim()),
+                }
+            },
+            Ok(_) => {
+                for value in computer.drain_outputs() {
+                    println!("{}", value);
+                }
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                return EXIT_FAULT;
+            }
+        }
+    }
+}
+
+/// Pass lines of stdin to the program and its text to stdout, printing any other output as a
+/// number on its own line.
+fn run_ascii(computer: Computer) -> i32 {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let mut ascii = AsciiComputer::new(computer);
+
+    loop {
+        let result = ascii.read_until_prompt();
+        if let Ok(text) = &result {
+            print!("{}", text);
+        }
+        for value in ascii.take_non_ascii() {
+            println!("{}", value);
+        }
+        let _ = io::stdout().flush();
+
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            return EXIT_FAULT;
+        }
+        if ascii.is_halted() {
+            return 0;
+        }
+
+        loop {
+            match read_line(&mut stdin) {
+                Some(line) if line.is_ascii() => {
+                    ascii.send_line(&line);
+                    break;
+                }
+                Some(_) => eprintln!("Only ASCII input is supported"),
+                None => return EXIT_STARVED,
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut ascii = false;
+    let mut patches = PatchSet::default();
+    let mut inputs = vec![];
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => ascii = true,
+            "--patch" => {
+                let patch = args
+                    .next()
+                    .unwrap_or_else(|| usage_error("Expected a patch after `--patch`"));
+                let patch = patch
+                    .parse()
+                    .unwrap_or_else(|error: ParseError| usage_error(&error.message));
+                patches.push(patch);
+            }
+            "--patches" => {
+                let path = args
+                    .next()
+                    .unwrap_or_else(|| usage_error("Expected a file after `--patches`"));
+                let set = fs::read_to_string(&path)
+                    .map_err(|error| error.to_string())
+                    .and_then(|text| text.parse::<PatchSet>().map_err(|error| error.to_string()))
+                    .unwrap_or_else(|error| {
+                        eprintln!("{}: {}", path, error);
+                        process::exit(EXIT_FAULT);
+                    });
+                patches = patches.combine(&set);
+            }
+            "--input" => {
+                let values = args
+                    .next()
+                    .unwrap_or_else(|| usage_error("Expected values after `--input`"));
+
+                for value in values.split(',').filter(|value| !value.trim().is_empty()) {
+                    match value.trim().parse::<isize>() {
+                        Ok(value) => inputs.push(value),
+                        Err(_) => usage_error(&format!("Invalid input `{}`", value)),
+                    }
+                }
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => usage_error(&format!("Unexpected argument `{}`", arg)),
+        }
+    }
+
+    let path = path.unwrap_or_else(|| usage_error("Expected a program"));
+    let program = Program::from_file(&path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(EXIT_FAULT);
+    });
+    let mut computer = Computer::new(program.words());
+
+    if let Err(reason) = patches.apply(computer.memory_mut()) {
+        eprintln!("Failed to patch the program: {:?}", reason);
+        process::exit(EXIT_FAULT);
+    }
+    computer.push_inputs(inputs);
+
+    let code = if ascii {
+        run_ascii(computer)
+    } else {
+        run_numeric(computer)
+    };
+
+    process::exit(code);
+}
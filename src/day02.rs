@@ -1,34 +1,12 @@
-use crate::intcode_computer::Computer;
-use crate::parse_custom_separated;
-use itertools::iproduct;
-
-fn run_until_halt(memory: Vec<i64>) -> i64 {
-    let mut numbers: Vec<i64> = memory;
-    let mut pc = 0;
-
-    while numbers[pc] != 99 {
-        let x_position = numbers[pc + 1] as usize;
-        let y_position = numbers[pc + 2] as usize;
-        let z_position = numbers[pc + 3] as usize;
-
-        match numbers[pc] {
-            1 => {
-                numbers[z_position] = numbers[x_position] + numbers[y_position];
-            }
-            2 => {
-                numbers[z_position] = numbers[x_position] * numbers[y_position];
-            }
-            _ => panic!("Unknown opcode {}", numbers[pc]),
-        }
-
-        pc += 4
-    }
-
-    numbers[0]
-}
+use crate::intcode::program::Program;
+use crate::intcode::symbolic::{Goal, Problem, Symbol};
+use crate::intcode_computer::{Computer, Memory};
 
 pub fn star_one(input: &str) -> isize {
-    let program = parse_custom_separated::<isize>(input, ",").collect();
+    let program = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::with_input(program, || Some(1));
     computer.run_until_halt_or_paused(false);
 
@@ -36,19 +14,22 @@ pub fn star_one(input: &str) -> isize {
 }
 
 pub fn star_two(input: &str) -> isize {
-    let program: Vec<_> = parse_custom_separated::<isize>(input, ",").collect();
-
-    let (noun, verb) = iproduct!((0..=99), (0..=99))
-        .find(|&(noun, verb)| {
-            let mut modified_computer = Computer::with_input(program.clone(), || Some(1));
-            modified_computer.memory().write(1, noun);
-            modified_computer.memory().write(2, verb);
-
-            modified_computer.run_until_halt_or_paused(false);
-
-            modified_computer.memory().read(0) == 19690720
-        })
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
+
+    let problem = Problem {
+        program,
+        unknowns: vec![(Symbol::Memory(1), 0..=99), (Symbol::Memory(2), 0..=99)],
+        goal: Goal::Memory(0),
+        target: 19690720,
+    };
+
+    let solution = problem
+        .solve()
         .expect("There should be a noun and verb that results in the output `19690720`");
+    let (noun, verb) = (solution.values[0], solution.values[1]);
 
     100 * noun + verb
 }
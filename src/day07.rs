@@ -1,67 +1,42 @@
-use crate::intcode_computer::{input_with_initial_value, Computer};
-use crate::parse_custom_separated;
+use crate::intcode::network::Network;
+use crate::intcode::program::Program;
+use crate::intcode::threaded::Pool;
+use crate::intcode_computer::Computer;
 
 use itertools::iproduct;
 
-use std::cell::RefCell;
 use std::collections::HashSet;
 use std::ops::RangeInclusive;
-use std::rc::Rc;
 
 fn thruster_signal(program: Vec<isize>, phase_settings: &[isize], feedback: bool) -> isize {
-    let mut computers: Vec<_> = phase_settings
-        .iter()
-        .map(|setting| Computer::new(program.clone()))
-        .collect();
-    let last_outputs: Rc<RefCell<Vec<Option<isize>>>> =
-        Rc::new(RefCell::new(phase_settings.iter().map(|_| None).collect()));
-
-    for (idx, setting) in phase_settings.iter().enumerate() {
-        let mut has_sent_initial_signal = false;
-        let outputs = Rc::clone(&last_outputs);
-        let last_outputs = Rc::clone(&last_outputs);
-
-        computers[idx].set_input(input_with_initial_value(*setting, move || {
-            if idx == 0 {
-                if feedback {
-                    if has_sent_initial_signal {
-                        let borrowed_outputs = last_outputs.borrow();
-
-                        borrowed_outputs[borrowed_outputs.len() - 1]
-                    } else {
-                        has_sent_initial_signal = true;
-
-                        Some(0)
-                    }
-                } else {
-                    Some(0)
-                }
-            } else {
-                outputs.borrow()[idx - 1]
-            }
-        }));
-    }
-
-    let mut all_halted = false;
-    while !all_halted {
-        let unhalted_computers = computers
-            .iter_mut()
-            .enumerate()
-            .filter(|(_, computer)| !computer.is_halted());
+    let mut network = Network::new();
+    for &setting in phase_settings {
+        let mut computer = Computer::new(program.clone());
+        computer.push_input(setting);
 
-        for (idx, computer) in unhalted_computers {
-            computer.run_until_halt_or_paused(true);
-            last_outputs.borrow_mut()[idx] = computer.last_output();
-        }
+        network.add(computer);
+    }
+    network.send(0, &[0]);
 
-        all_halted = computers.iter().all(|computer| computer.is_halted())
+    let last_idx = network.len() - 1;
+    for idx in 0..last_idx {
+        network.connect(&format!("{}->{}", idx, idx + 1), idx, idx + 1);
+    }
+    if feedback {
+        network.connect("feedback", last_idx, 0);
     }
 
-    {
-        let max_thruster_signal = last_outputs.borrow()[phase_settings.len() - 1].unwrap_or(0);
+    network
+        .run()
+        .expect("The amplifiers should run without faulting");
 
-        max_thruster_signal
-    }
+    let last_signal = if feedback {
+        network.channel("feedback").and_then(|channel| channel.last)
+    } else {
+        network.machine(last_idx).last_output()
+    };
+
+    last_signal.unwrap_or(0)
 }
 
 fn find_max_thruster_signal(
@@ -69,36 +44,42 @@ fn find_max_thruster_signal(
     phaser_range: RangeInclusive<isize>,
     feedback: bool,
 ) -> isize {
-    iproduct!(
+    let phase_settings = iproduct!(
         phaser_range.clone(),
         phaser_range.clone(),
         phaser_range.clone(),
         phaser_range.clone(),
         phaser_range.clone()
     )
-    .filter_map(|(p1, p2, p3, p4, p5)| {
-        let phase_setting = [p1, p2, p3, p4, p5];
-        let unique_settings: HashSet<_> = phase_setting.into_iter().collect();
-
-        if unique_settings.len() < phase_setting.len() {
-            None
-        } else {
-            let result = thruster_signal(program.clone(), &phase_setting, feedback);
-
-            Some(result)
-        }
-    })
-    .max()
-    .expect("There should be a max thruster signal")
+    .map(|(p1, p2, p3, p4, p5)| [p1, p2, p3, p4, p5])
+    .filter(|phase_setting| {
+        let unique_settings: HashSet<_> = phase_setting.iter().collect();
+
+        unique_settings.len() == phase_setting.len()
+    });
+
+    Pool::with_available_parallelism()
+        .map(phase_settings, |phase_setting| {
+            thruster_signal(program.clone(), &phase_setting, feedback)
+        })
+        .into_iter()
+        .max()
+        .expect("There should be a max thruster signal")
 }
 
 pub fn star_one(input: &str) -> isize {
-    let program: Vec<_> = parse_custom_separated::<isize>(input, ",").collect();
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     find_max_thruster_signal(program, 0..=4, false)
 }
 
 pub fn star_two(input: &str) -> isize {
-    let program: Vec<_> = parse_custom_separated::<isize>(input, ",").collect();
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     find_max_thruster_signal(program, 5..=9, true)
 }
 
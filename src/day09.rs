@@ -1,8 +1,11 @@
+use crate::intcode::program::Program;
 use crate::intcode_computer::Computer;
-use crate::parse_custom_separated;
 
 pub fn star_one(input: &str) -> isize {
-    let program: Vec<_> = parse_custom_separated::<isize>(input, ",").collect();
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::with_input(program, || Some(1));
 
     computer.run_until_halt_or_paused(false);
@@ -13,7 +16,10 @@ pub fn star_one(input: &str) -> isize {
 }
 
 pub fn star_two(input: &str) -> isize {
-    let program: Vec<_> = parse_custom_separated::<isize>(input, ",").collect();
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::with_input(program, || Some(2));
 
     computer.run_until_halt_or_paused(false);
@@ -1,10 +1,9 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::f64::consts::PI;
 
-use crate::intcode_computer::Computer;
-use crate::parse_custom_separated;
+use crate::intcode::program::Program;
+use crate::intcode_computer::{Computer, ExecutionState};
 
 #[derive(Debug, Copy, Clone)]
 enum Color {
@@ -34,88 +33,81 @@ type Location = (isize, isize);
 
 pub fn star_one(input: &str) -> usize {
     let mut direction = PI / 2.0;
-    let mut current_location = RefCell::new((0, 0));
-    let mut grid = RefCell::new(HashMap::<Location, Color>::new());
-    let program = parse_custom_separated::<isize>(input, ",").collect();
+    let mut current_location = (0, 0);
+    let mut grid = HashMap::<Location, Color>::new();
+    let program = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::new(program);
-    computer.set_input(|| {
-        grid.borrow()
-            .get(&current_location.borrow())
-            .map(|&color| color as isize)
-            .or(Some(Color::default() as isize))
-    });
-
-    while !computer.is_halted() {
-        computer.run_until_halt_or_paused(true);
-        let new_paint = Color::try_from(computer.last_output().unwrap())
-            .expect("Should be able to parse value as color");
-
-        grid.borrow_mut()
-            .entry(current_location.borrow().clone())
-            .and_modify(|entry| *entry = new_paint)
-            .or_insert(new_paint);
-
-        computer.run_until_halt_or_paused(true);
-        match computer.last_output() {
-            Some(0) => direction += PI / 2.0,
-            Some(1) => direction -= PI / 2.0,
+
+    loop {
+        let current_color = grid.get(&current_location).cloned().unwrap_or_default();
+        computer.push_input(current_color as isize);
+
+        let new_paint = match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(value) => {
+                Color::try_from(value).expect("Should be able to parse value as color")
+            }
+            ExecutionState::Halted => break,
+            state => panic!("Unexpected execution state {:?}", state),
+        };
+
+        grid.insert(current_location, new_paint);
+
+        match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(0) => direction += PI / 2.0,
+            ExecutionState::Output(1) => direction -= PI / 2.0,
             _ => panic!("Invalid direction"),
         }
 
-        current_location.replace_with(|location| {
-            (
-                location.0 + direction.cos() as isize,
-                location.1 + direction.sin() as isize,
-            )
-        });
+        current_location = (
+            current_location.0 + direction.cos() as isize,
+            current_location.1 + direction.sin() as isize,
+        );
     }
 
-    {
-        let grid = grid.borrow();
-        grid.len()
-    }
+    grid.len()
 }
 
 pub fn star_two(input: &str) -> String {
     let mut direction = PI / 2.0;
-    let current_location = RefCell::new((0, 0));
-    let grid = RefCell::new(HashMap::<Location, Color>::new());
-    grid.borrow_mut().insert((0, 0), Color::White);
-    let program = parse_custom_separated::<isize>(input, ",").collect();
+    let mut current_location = (0, 0);
+    let mut grid = HashMap::<Location, Color>::new();
+    grid.insert((0, 0), Color::White);
+    let program = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::new(program);
-    computer.set_input(|| {
-        grid.borrow()
-            .get(&current_location.borrow())
-            .map(|&color| color as isize)
-            .or(Some(Color::default() as isize))
-    });
-
-    while !computer.is_halted() {
-        computer.run_until_halt_or_paused(true);
-        let new_paint = Color::try_from(computer.last_output().unwrap())
-            .expect("Should be able to parse value as color");
-
-        grid.borrow_mut()
-            .entry(current_location.borrow().clone())
-            .and_modify(|entry| *entry = new_paint)
-            .or_insert(new_paint);
-
-        computer.run_until_halt_or_paused(true);
-        match computer.last_output() {
-            Some(0) => direction -= PI / 2.0,
-            Some(1) => direction += PI / 2.0,
+
+    loop {
+        let current_color = grid.get(&current_location).cloned().unwrap_or_default();
+        computer.push_input(current_color as isize);
+
+        let new_paint = match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(value) => {
+                Color::try_from(value).expect("Should be able to parse value as color")
+            }
+            ExecutionState::Halted => break,
+            state => panic!("Unexpected execution state {:?}", state),
+        };
+
+        grid.insert(current_location, new_paint);
+
+        match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(0) => direction -= PI / 2.0,
+            ExecutionState::Output(1) => direction += PI / 2.0,
             _ => panic!("Invalid direction"),
         }
 
-        current_location.replace_with(|location| {
-            (
-                location.0 + direction.cos().round() as isize,
-                location.1 + direction.sin().round() as isize,
-            )
-        });
+        current_location = (
+            current_location.0 + direction.cos().round() as isize,
+            current_location.1 + direction.sin().round() as isize,
+        );
     }
 
-    let (max, min) = grid.borrow().keys().fold(
+    let (max, min) = grid.keys().fold(
         (
             (std::isize::MIN, std::isize::MIN),
             (std::isize::MAX, std::isize::MAX),
@@ -144,15 +136,14 @@ pub fn star_two(input: &str) -> String {
         },
     );
 
-    let borrowed_grid = &grid.borrow();
+    let grid = &grid;
     (min.1 - 1..=max.1 + 1)
         .rev()
         .map(|y| {
             (min.0 - 1..=max.0 + 1)
                 .rev()
                 .map(move |x| {
-                    borrowed_grid
-                        .get(&(x, y))
+                    grid.get(&(x, y))
                         .map(|color| match color {
                             Color::Black => '.',
                             Color::White => '#',
@@ -1,9 +1,9 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 
-use crate::intcode_computer::Computer;
-use crate::parse_custom_separated;
+use crate::intcode::patch::{Patch, PatchSet};
+use crate::intcode::program::Program;
+use crate::intcode_computer::{Computer, ExecutionState};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 enum Tile {
@@ -31,24 +31,30 @@ impl TryFrom<isize> for Tile {
 
 type Location = (isize, isize);
 
+fn next_output(computer: &mut Computer) -> Option<isize> {
+    match computer.run_until_halt_or_paused(true) {
+        ExecutionState::Output(value) => Some(value),
+        ExecutionState::Halted => None,
+        state => panic!("Unexpected execution state {:?}", state),
+    }
+}
+
 pub fn star_one(input: &str) -> usize {
-    let program: Vec<isize> = parse_custom_separated(input, ",").collect();
+    let program: Vec<isize> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
     let mut computer = Computer::new(program);
     computer.set_input(|| Some(0));
     let mut tiles: HashMap<Location, Tile> = HashMap::new();
 
-    while !computer.is_halted() {
-        computer.run_until_halt_or_paused(true);
-        // X
-        let x = computer.last_output().unwrap();
-
-        computer.run_until_halt_or_paused(true);
+    // X
+    while let Some(x) = next_output(&mut computer) {
         // Y
-        let y = computer.last_output().unwrap();
+        let y = next_output(&mut computer).unwrap();
 
-        computer.run_until_halt_or_paused(true);
         // Tile
-        let tile = Tile::try_from(computer.last_output().unwrap()).unwrap();
+        let tile = Tile::try_from(next_output(&mut computer).unwrap()).unwrap();
 
         tiles
             .entry((x, y))
@@ -59,35 +65,43 @@ pub fn star_one(input: &str) -> usize {
     tiles.values().filter(|&tile| tile == &Tile::Block).count()
 }
 
+/// Lets the game be played without inserting quarters.
+pub fn free_play() -> PatchSet {
+    PatchSet::named("Free play", vec![Patch::new(0, 2)])
+}
+
 pub fn star_two(input: &str) -> isize {
-    let mut program: Vec<isize> = parse_custom_separated(input, ",").collect();
-    program[0] = 2; // Add quarters
-    let mut computer = Computer::new(program);
-    let next_input: RefCell<isize> = RefCell::new(0);
-    computer.set_input(|| Some(*next_input.borrow()));
+    let program = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .patched(&free_play());
+    let mut computer = Computer::new(program.words());
+    let mut next_input = 0;
     let mut tiles: HashMap<Location, Tile> = HashMap::new();
     let mut score = None;
     let mut paddle_target_location: Option<isize> = None;
     let mut paddle_location: Option<isize> = None;
 
     loop {
-        computer.run_until_halt_or_paused(true);
         // X
-        let x = computer.last_output().unwrap();
+        let x = match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(x) => x,
+            ExecutionState::NeedsInput => {
+                computer.push_input(next_input);
+                continue;
+            }
+            ExecutionState::Halted => break,
+            state => panic!("Unexpected execution state {:?}", state),
+        };
 
-        if x == -1 || computer.is_halted() {
-            computer.run_until_halt_or_paused(true);
-            computer.run_until_halt_or_paused(true);
+        // Y
+        let y = next_output(&mut computer).unwrap();
 
-            score = computer.last_output();
+        if x == -1 && y == 0 {
+            score = next_output(&mut computer);
         } else {
-            computer.run_until_halt_or_paused(true);
-            // Y
-            let y = computer.last_output().unwrap();
-
-            computer.run_until_halt_or_paused(true);
             // Tile
-            let tile = Tile::try_from(computer.last_output().unwrap()).unwrap();
+            let tile = Tile::try_from(next_output(&mut computer).unwrap()).unwrap();
 
             match tile {
                 Tile::Ball => {
@@ -99,7 +113,7 @@ pub fn star_two(input: &str) -> isize {
                 _ => (),
             }
 
-            *next_input.borrow_mut() = paddle_target_location
+            next_input = paddle_target_location
                 .and_then(|target_location| {
                     paddle_location.map(|paddle_location| {
                         if target_location < paddle_location {
@@ -118,10 +132,6 @@ pub fn star_two(input: &str) -> isize {
                 .and_modify(|e| *e = tile)
                 .or_insert(tile);
         }
-
-        if !tiles.values().any(|&v| v == Tile::Block) && computer.is_halted() {
-            break;
-        }
     }
 
     score.unwrap()
@@ -1,11 +1,10 @@
-use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::fmt;
 
-use crate::intcode_computer::Computer;
+use crate::intcode::program::Program;
+use crate::intcode_computer::{Computer, ExecutionState};
 use crate::math::Vector2;
-use crate::parse_custom_separated;
 
 pub type Location = Vector2<isize>;
 
@@ -357,25 +356,30 @@ impl fmt::Debug for World {
 }
 
 fn explore_world(input: &str) -> World {
-    let program: Vec<isize> = parse_custom_separated(input, ",").collect();
-    let next_input: RefCell<Direction> = RefCell::new(Direction::North);
+    let program: Vec<isize> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
+    let mut direction = Direction::North;
     let mut world = World::new();
     let mut computer = Computer::new(program);
-    computer.set_input(|| Some((*next_input.borrow()) as isize));
 
     loop {
-        computer.run_until_halt_or_paused(true);
-        let status = Status::try_from(computer.last_output().unwrap())
-            .expect("Expected to be able to parse status");
+        computer.push_input(direction as isize);
+        let status = match computer.run_until_halt_or_paused(true) {
+            ExecutionState::Output(value) => {
+                Status::try_from(value).expect("Expected to be able to parse status")
+            }
+            state => panic!("Unexpected execution state {:?}", state),
+        };
 
-        let direction = *next_input.borrow();
         world.update(direction, status);
 
         if world.explored_everything {
             break;
         }
 
-        *next_input.borrow_mut() = world.next_direction();
+        direction = world.next_direction();
     }
 
     world
@@ -1,22 +1,309 @@
-pub fn star_one(input: &str) -> i64 {
-    0
+use crate::intcode::ascii::AsciiComputer;
+use crate::intcode::patch::{Patch, PatchSet};
+use crate::intcode::program::Program;
+use crate::intcode_computer::Computer;
+
+/// The longest movement function or main routine the robot accepts, in characters.
+const MAX_ROUTINE_LENGTH: usize = 20;
+const FUNCTION_NAMES: [&str; 3] = ["A", "B", "C"];
+
+type Scaffold = Vec<Vec<char>>;
+
+fn camera_view(program: Vec<isize>) -> Scaffold {
+    let mut ascii = AsciiComputer::new(Computer::new(program));
+    let view = ascii
+        .read_until_prompt()
+        .expect("The camera should run without faulting");
+
+    parse_view(&view)
+}
+
+fn parse_view(view: &str) -> Scaffold {
+    view.lines()
+        .map(|line| line.trim().chars().collect::<Vec<_>>())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn tile(scaffold: &Scaffold, x: isize, y: isize) -> char {
+    if x < 0 || y < 0 {
+        return '.';
+    }
+
+    scaffold
+        .get(y as usize)
+        .and_then(|row| row.get(x as usize))
+        .cloned()
+        .unwrap_or('.')
+}
+
+fn is_scaffold(scaffold: &Scaffold, x: isize, y: isize) -> bool {
+    tile(scaffold, x, y) != '.'
+}
+
+fn alignment_parameters(scaffold: &Scaffold) -> isize {
+    let mut sum = 0;
+
+    for (y, row) in scaffold.iter().enumerate() {
+        for x in 0..row.len() {
+            let (x, y) = (x as isize, y as isize);
+            let is_intersection = [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .all(|(dx, dy)| is_scaffold(scaffold, x + dx, y + dy));
+
+            if is_intersection {
+                sum += x * y;
+            }
+        }
+    }
+
+    sum
+}
+
+/// The turns and forward moves that take the robot over the whole scaffold, e.g. `R,8`.
+fn path(scaffold: &Scaffold) -> Vec<String> {
+    let (mut x, mut y, mut direction) = scaffold
+        .iter()
+        .enumerate()
+        .find_map(|(y, row)| {
+            row.iter().enumerate().find_map(|(x, &c)| {
+                let direction = match c {
+                    '^' => (0, -1),
+                    'v' => (0, 1),
+                    '<' => (-1, 0),
+                    '>' => (1, 0),
+                    _ => return None,
+                };
+
+                Some((x as isize, y as isize, direction))
+            })
+        })
+        .expect("The scaffold should contain the robot");
+    let mut path = vec![];
+
+    loop {
+        let (dx, dy) = direction;
+        let (turn, new_direction) = if is_scaffold(scaffold, x + dy, y - dx) {
+            ("L", (dy, -dx))
+        } else if is_scaffold(scaffold, x - dy, y + dx) {
+            ("R", (-dy, dx))
+        } else {
+            return path;
+        };
+
+        direction = new_direction;
+        let mut steps = 0;
+        while is_scaffold(scaffold, x + direction.0, y + direction.1) {
+            x += direction.0;
+            y += direction.1;
+            steps += 1;
+        }
+
+        path.push(format!("{},{}", turn, steps));
+    }
+}
+
+fn routine_length(moves: &[String]) -> usize {
+    moves.iter().map(|m| m.len() + 1).sum::<usize>().max(1) - 1
+}
+
+/// Split `rest` into calls to at most three movement functions, extending `functions` and
+/// `main` with the solution.
+fn compress<'a>(
+    rest: &'a [String],
+    functions: &mut Vec<&'a [String]>,
+    main: &mut Vec<usize>,
+) -> bool {
+    if main.len() * 2 > MAX_ROUTINE_LENGTH + 1 {
+        return false;
+    }
+
+    if rest.is_empty() {
+        return true;
+    }
+
+    for idx in 0..functions.len() {
+        if rest.starts_with(functions[idx]) {
+            main.push(idx);
+            if compress(&rest[functions[idx].len()..], functions, main) {
+                return true;
+            }
+            main.pop();
+        }
+    }
+
+    if functions.len() < FUNCTION_NAMES.len() {
+        for len in 1..=rest.len() {
+            if routine_length(&rest[..len]) > MAX_ROUTINE_LENGTH {
+                break;
+            }
+
+            functions.push(&rest[..len]);
+            main.push(functions.len() - 1);
+            if compress(&rest[len..], functions, main) {
+                return true;
+            }
+            main.pop();
+            functions.pop();
+        }
+    }
+
+    false
 }
 
-pub fn star_two(input: &str) -> i64 {
-    0
+/// The main routine followed by the movement functions that walk `path`.
+fn movement_routines(path: &[String]) -> Vec<String> {
+    let mut functions = vec![];
+    let mut main = vec![];
+
+    if !compress(path, &mut functions, &mut main) {
+        panic!("The path can't be split into three movement functions");
+    }
+
+    let main: Vec<_> = main.iter().map(|&idx| FUNCTION_NAMES[idx]).collect();
+    let mut routines = vec![main.join(",")];
+    routines.extend(functions.iter().map(|function| function.join(",")));
+    // The robot asks for every function, even the ones the main routine doesn't call
+    routines.resize(FUNCTION_NAMES.len() + 1, "L,0".to_string());
+
+    routines
+}
+
+pub fn star_one(input: &str) -> isize {
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
+
+    alignment_parameters(&camera_view(program))
+}
+
+/// Wakes the vacuum robot up, so it asks for movement routines.
+pub fn wake_up() -> PatchSet {
+    PatchSet::named("Wake up", vec![Patch::new(0, 2)])
+}
+
+pub fn star_two(input: &str) -> isize {
+    let program = input.parse::<Program>().expect("Invalid Intcode program");
+    let routines = movement_routines(&path(&camera_view(program.words())));
+
+    let computer = Computer::new(program.patched(&wake_up()).words());
+    let mut ascii = AsciiComputer::new(computer);
+
+    for routine in routines.iter().map(String::as_str).chain(Some("n")) {
+        ascii
+            .read_until_prompt()
+            .expect("The robot should run without faulting");
+        ascii.send_line(routine);
+    }
+
+    let report = ascii
+        .read_until_prompt()
+        .expect("The robot should run without faulting");
+
+    ascii
+        .take_non_ascii()
+        .pop()
+        .unwrap_or_else(|| panic!("The robot didn't report any dust:\n{}", report))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{star_one, star_two};
+    use super::{alignment_parameters, movement_routines, parse_view, path, star_one, star_two};
+
+    const INTERSECTIONS: &str = "
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..";
+
+    const FULL_PATH: &str = "
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......";
+
+    const TWO_TURNS: &str = "
+^####
+....#
+....#";
+
+    // Prints the view, or once woken up reads the routines and the video feed answer and outputs
+    // the sum of every character read times its position
+    const ROBOT: [isize; 47] = [
+        1, 39, 40, 41, // [41] = 1 + 0, or 1 * 0 once woken up
+        1005, 41, 47, // Print the view
+        3, 42, // Read a character
+        1001, 43, 1, 43, // position += 1
+        2, 42, 43, 44, 1, 45, 44, 45, // sum += character * position
+        1008, 42, 10, 44, 1, 46, 44, 46, // lines += character == '\n'
+        1008, 46, 5, 44, 1006, 44, 7, // Read the next character until all five lines are in
+        4, 45, 99, // Report the sum
+        1, 0, 0, 0, 0, 0, 0, 0, // One, zero, camera, character, position, temp, sum, lines
+    ];
+
+    fn print_view(mut program: Vec<isize>, view: &str) -> String {
+        for c in view.chars() {
+            program.extend(vec![104, c as isize]);
+        }
+        program.push(99);
+
+        let program: Vec<_> = program.iter().map(ToString::to_string).collect();
+        program.join(",")
+    }
 
     #[test]
     fn test_star_one() {
-        assert_eq!(star_one(""), 1)
+        // Prints the view and halts
+        let program = print_view(vec![], INTERSECTIONS);
+
+        assert_eq!(star_one(&program), 76);
+        assert_eq!(alignment_parameters(&parse_view(INTERSECTIONS)), 76);
+    }
+
+    #[test]
+    fn test_movement_routines() {
+        let path = path(&parse_view(FULL_PATH));
+        assert_eq!(
+            path.join(","),
+            "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2"
+        );
+
+        let routines = movement_routines(&path);
+        let expanded: Vec<_> = routines[0]
+            .split(',')
+            .map(|name| match name {
+                "A" => routines[1].as_str(),
+                "B" => routines[2].as_str(),
+                _ => routines[3].as_str(),
+            })
+            .collect();
+
+        assert_eq!(expanded.join(","), path.join(","));
+        assert!(routines.iter().all(|routine| routine.len() <= 20));
     }
 
     #[test]
     fn test_star_two() {
-        assert_eq!(star_two(""), 1)
+        let program = print_view(ROBOT.to_vec(), TWO_TURNS);
+        // Two movement functions are enough, the third is still sent
+        let sent = "A,B\nR,4\nR,2\nL,0\nn\n";
+        let sum: isize = sent.bytes().zip(1..).map(|(c, idx)| c as isize * idx).sum();
+
+        assert_eq!(star_two(&program), sum);
     }
 }
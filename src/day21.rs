@@ -1,22 +1,95 @@
-pub fn star_one(input: &str) -> i64 {
-    0
+use crate::intcode::ascii::AsciiComputer;
+use crate::intcode::program::Program;
+use crate::intcode_computer::Computer;
+
+/// Jump if there's a hole in the next three tiles and ground to land on.
+const WALK_SCRIPT: [&str; 7] = [
+    "NOT A J", "NOT B T", "OR T J", "NOT C T", "OR T J", "AND D J", "WALK",
+];
+
+/// Like walking, but only jump when it's possible to either step or jump again after landing.
+const RUN_SCRIPT: [&str; 11] = [
+    "NOT A J", "NOT B T", "OR T J", "NOT C T", "OR T J", "AND D J", "NOT E T", "NOT T T", "OR H T",
+    "AND T J", "RUN",
+];
+
+fn hull_damage(input: &str, script: &[&str]) -> isize {
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
+    let mut ascii = AsciiComputer::new(Computer::new(program));
+
+    ascii
+        .read_until_prompt()
+        .expect("The springdroid should run without faulting");
+    for instruction in script {
+        ascii.send_line(instruction);
+    }
+
+    let report = ascii
+        .read_until_prompt()
+        .expect("The springdroid should run without faulting");
+
+    ascii
+        .take_non_ascii()
+        .pop()
+        .unwrap_or_else(|| panic!("The springdroid fell into space:\n{}", report))
 }
 
-pub fn star_two(input: &str) -> i64 {
-    0
+pub fn star_one(input: &str) -> isize {
+    hull_damage(input, &WALK_SCRIPT)
+}
+
+pub fn star_two(input: &str) -> isize {
+    hull_damage(input, &RUN_SCRIPT)
 }
 
 #[cfg(test)]
 mod tests {
     use super::{star_one, star_two};
+    use crate::intcode::asm::assemble;
+
+    // Prompts for a script and reports a thousand for each line it received once the script
+    // ends with `WALK` or `RUN`
+    const SPRINGDROID: &str = "
+        out #63
+        out #10
+loop:   in [c]
+        eq [c], #10, [t]
+        jz [t], #other
+        add [lines], #1, [lines]
+        eq [previous], #75, [t]
+        jnz [t], #done
+        eq [previous], #78, [t]
+        jnz [t], #done
+other:  add [c], #0, [previous]
+        jnz #1, #loop
+done:   mul [lines], #1000, [lines]
+        out [lines]
+        hlt
+c:      .data 0
+t:      .data 0
+lines:  .data 0
+previous: .data 0";
+
+    fn program() -> String {
+        let words: Vec<_> = assemble(SPRINGDROID)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        words.join(",")
+    }
 
     #[test]
     fn test_star_one() {
-        assert_eq!(star_one(""), 1)
+        assert_eq!(star_one(&program()), 7000);
     }
 
     #[test]
     fn test_star_two() {
-        assert_eq!(star_two(""), 1)
+        assert_eq!(star_two(&program()), 11000);
     }
 }
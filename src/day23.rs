@@ -1,22 +1,155 @@
-pub fn star_one(input: &str) -> i64 {
-    0
+use crate::intcode::network::{Network, NetworkState};
+use crate::intcode::program::Program;
+use crate::intcode_computer::Computer;
+
+const COMPUTERS: isize = 50;
+const NAT_ADDRESS: usize = 255;
+
+fn network(input: &str) -> Network {
+    let program: Vec<_> = input
+        .parse::<Program>()
+        .expect("Invalid Intcode program")
+        .words();
+    let mut network = Network::new();
+    network.set_packet_routing(3);
+    network.set_idle_input(Some(-1));
+
+    for address in 0..COMPUTERS {
+        let mut computer = Computer::new(program.clone());
+        computer.push_input(address);
+
+        network.add(computer);
+    }
+
+    network
 }
 
-pub fn star_two(input: &str) -> i64 {
-    0
+pub fn star_one(input: &str) -> isize {
+    let mut network = network(input);
+
+    loop {
+        let state = network
+            .step()
+            .expect("The network should run without faulting");
+
+        let nat_packet = network
+            .take_undelivered()
+            .into_iter()
+            .find(|packet| packet.to == NAT_ADDRESS);
+
+        if let Some(packet) = nat_packet {
+            return packet.values[1];
+        }
+
+        match state {
+            Some(NetworkState::Halted) => {
+                panic!("The network halted before sending anything to the NAT")
+            }
+            Some(NetworkState::Idle) => {
+                panic!("The network went idle before sending anything to the NAT")
+            }
+            None => (),
+        }
+    }
+}
+
+pub fn star_two(input: &str) -> isize {
+    let mut network = network(input);
+    let mut nat_packet = None;
+    let mut last_delivered_y = None;
+
+    loop {
+        let state = network
+            .run()
+            .expect("The network should run without faulting");
+        nat_packet = network
+            .take_undelivered()
+            .into_iter()
+            .rfind(|packet| packet.to == NAT_ADDRESS)
+            .map(|packet| packet.values)
+            .or(nat_packet);
+
+        if state == NetworkState::Halted {
+            panic!("The network halted before the NAT delivered the same value twice");
+        }
+
+        let values = nat_packet
+            .clone()
+            .expect("The network went idle before sending anything to the NAT");
+
+        if last_delivered_y == Some(values[1]) {
+            return values[1];
+        }
+
+        last_delivered_y = Some(values[1]);
+        network.send(0, &values);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{star_one, star_two};
+    use crate::intcode::asm::assemble;
+
+    // Computer 0 sends 10,20 to computer 1 and every computer forwards packets to the next one,
+    // the last to the NAT. Computer 0 increments the Y of packets it receives up to 25.
+    const NIC: &str = "
+        in [address]
+        jnz [address], #poll
+        out #1
+        out #10
+        out #20
+poll:   in [x]
+        eq [x], #-1, [t]
+        jnz [t], #poll
+        in [y]
+        jnz [address], #forward
+        lt [y], #25, [t]
+        add [y], [t], [y]
+forward: add [address], #1, [to]
+        eq [to], #50, [t]
+        jz [t], #send
+        add #255, #0, [to]
+send:   out [to]
+        out [x]
+        out [y]
+        jnz #1, #poll
+address: .data 0
+x:      .data 0
+y:      .data 0
+t:      .data 0
+to:     .data 0";
+
+    fn program() -> String {
+        let words: Vec<_> = assemble(NIC)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        words.join(",")
+    }
 
     #[test]
     fn test_star_one() {
-        assert_eq!(star_one(""), 1)
+        assert_eq!(star_one(&program()), 20);
     }
 
     #[test]
     fn test_star_two() {
-        assert_eq!(star_two(""), 1)
+        assert_eq!(star_two(&program()), 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "The network halted before sending anything to the NAT")]
+    fn test_star_one_halted() {
+        star_one("99");
+    }
+
+    #[test]
+    #[should_panic(expected = "The network went idle before sending anything to the NAT")]
+    fn test_star_one_idle() {
+        // Reads its address and polls forever
+        star_one("3,100,3,101,1105,1,2");
     }
 }
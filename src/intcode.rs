@@ -0,0 +1,21 @@
+//! Tooling for working with Intcode programs outside of the
+//! [`Computer`](crate::intcode_computer::Computer) itself.
+
+pub mod ascii;
+pub mod asm;
+pub mod cfg;
+pub mod compile;
+pub mod debugger;
+pub mod disasm;
+#[cfg(feature = "extensions")]
+pub mod extensions;
+pub mod fuzz;
+pub mod network;
+pub mod parse;
+pub mod patch;
+pub mod program;
+pub mod record;
+pub mod snapshot;
+pub mod symbolic;
+pub mod threaded;
+pub mod trace;
@@ -0,0 +1,142 @@
+//! Talking to Intcode programs that communicate in ASCII text.
+use std::collections::VecDeque;
+
+use crate::intcode_computer::{Computer, ExecutionState, IntcodeError, Memory, PagedMemory};
+
+/// Whether `value` is an ASCII character, anything else is typically a final answer.
+pub fn is_ascii(value: isize) -> bool {
+    (0..128).contains(&value)
+}
+
+/// A [`Computer`] whose input and output are lines of text.
+///
+/// Outputs that aren't ASCII characters are kept apart from the text, in the order they were
+/// produced, see [`AsciiComputer::take_non_ascii`].
+pub struct AsciiComputer<M: Memory<Word = isize> = PagedMemory> {
+    computer: Computer<M>,
+    /// Text that was output but not read yet.
+    text: VecDeque<char>,
+    non_ascii: Vec<isize>,
+}
+
+impl<M: Memory<Word = isize>> AsciiComputer<M> {
+    pub fn new(computer: Computer<M>) -> Self {
+        Self {
+            computer,
+            text: VecDeque::new(),
+            non_ascii: vec![],
+        }
+    }
+
+    pub fn computer(&self) -> &Computer<M> {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer<M> {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Computer<M> {
+        self.computer
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.computer.is_halted()
+    }
+
+    /// Queue `line` followed by a newline as input.
+    ///
+    /// Panics if `line` isn't ASCII.
+    pub fn send_line(&mut self, line: &str) {
+        assert!(line.is_ascii(), "`{}` isn't ASCII", line);
+
+        self.computer
+            .push_inputs(line.bytes().chain(Some(b'\n')).map(isize::from));
+    }
+
+    /// Run until the program outputs a full line, which is returned without its newline.
+    ///
+    /// Returns `None` if the program halts or waits for input first, any partial line is kept
+    /// for the next read.
+    pub fn read_line(&mut self) -> Result<Option<String>, IntcodeError> {
+        loop {
+            if let Some(end) = self.text.iter().position(|&c| c == '\n') {
+                let line = self.text.drain(..=end).take(end).collect();
+
+                return Ok(Some(line));
+            }
+
+            match self.computer.try_run(true)? {
+                ExecutionState::Output(_) => self.collect_outputs(),
+                _ => {
+                    self.collect_outputs();
+
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Run until the program halts or waits for input, returning all text it output that
+    /// hasn't been read yet.
+    pub fn read_until_prompt(&mut self) -> Result<String, IntcodeError> {
+        self.computer.try_run(false)?;
+        self.collect_outputs();
+
+        Ok(self.text.drain(..).collect())
+    }
+
+    /// Take the outputs that weren't ASCII characters, oldest first.
+    pub fn take_non_ascii(&mut self) -> Vec<isize> {
+        std::mem::take(&mut self.non_ascii)
+    }
+
+    fn collect_outputs(&mut self) {
+        for value in self.computer.drain_outputs() {
+            if is_ascii(value) {
+                self.text.push_back(value as u8 as char);
+            } else {
+                self.non_ascii.push(value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsciiComputer;
+    use crate::intcode::asm::assemble;
+    use crate::intcode_computer::Computer;
+
+    #[test]
+    fn test_lines_and_prompts() {
+        // Prints a prompt, echoes a line of input back and outputs its length
+        let program = assemble(
+            "       out #62\n\
+                    out #10\n\
+             loop:  in [c]\n\
+                    out [c]\n\
+                    eq [c], #10, [t]\n\
+                    jnz [t], #done\n\
+                    add [n], #1000, [n]\n\
+                    jnz #1, #loop\n\
+             done:  out [n]\n\
+                    hlt\n\
+             c:     .data 0\n\
+             t:     .data 0\n\
+             n:     .data 0",
+        )
+        .unwrap();
+        let mut ascii = AsciiComputer::new(Computer::new(program));
+
+        assert_eq!(ascii.read_line(), Ok(Some(">".to_string())));
+        assert_eq!(ascii.read_line(), Ok(None));
+
+        ascii.send_line("hi");
+        assert_eq!(ascii.read_until_prompt(), Ok("hi\n".to_string()));
+        assert!(ascii.is_halted());
+        assert_eq!(ascii.take_non_ascii(), vec![2000]);
+        assert_eq!(ascii.take_non_ascii(), vec![]);
+        assert_eq!(ascii.read_line(), Ok(None));
+    }
+}
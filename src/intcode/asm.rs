@@ -0,0 +1,762 @@
+//! An assembler for the textual Intcode syntax produced by [`disasm`](super::disasm).
+//!
+//! ```text
+//! ; Echo input until a zero is read
+//! loop:   in [value]
+//!         jz [value], #done
+//!         out [value]
+//!         jnz #1, #loop
+//! done:   hlt
+//! value:  .data 0
+//! ```
+//!
+//! Operands are written `#imm`, `[pos]` or `[rb+n]`, and anywhere a number is expected a label,
+//! optionally offset with `+` or `-`, can be used instead. A line may start with one or more
+//! `label:` definitions, or with a numeric `address:` that must match the address the line is
+//! assembled at, which lets listings from the disassembler be assembled again.
+//!
+//! Macros are defined with `.macro name param, ...` and closed with `.endm`. When invoked as
+//! `name operand, ...` each parameter is replaced by the matching operand, so parameters stand
+//! in for whole operands:
+//!
+//! ```text
+//! .macro push value
+//!         add value, #0, [rb+0]
+//!         arb #1
+//! .endm
+//!         push #5
+//! ```
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+const MAX_MACRO_DEPTH: usize = 64;
+
+/// An error found while assembling, `line` and `column` both start from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new<S: Into<String>>(line: usize, column: usize, message: S) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Opcode, parameter count and which parameter, if any, is written to.
+fn mnemonic(name: &str) -> Option<(isize, usize, Option<usize>)> {
+    match name {
+        "add" => Some((1, 3, Some(2))),
+        "mul" => Some((2, 3, Some(2))),
+        "in" => Some((3, 1, Some(0))),
+        "out" => Some((4, 1, None)),
+        "jnz" => Some((5, 2, None)),
+        "jz" => Some((6, 2, None)),
+        "lt" => Some((7, 3, Some(2))),
+        "eq" => Some((8, 3, Some(2))),
+        "arb" => Some((9, 1, None)),
+        "hlt" => Some((99, 0, None)),
+        _ => None,
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.'
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// A line of source after macro expansion, `text` has had its comment removed.
+#[derive(Debug, Clone)]
+struct SourceLine {
+    line: usize,
+    text: String,
+}
+
+impl SourceLine {
+    fn error<S: Into<String>>(&self, offset: usize, message: S) -> AsmError {
+        AsmError::new(self.line, offset + 1, message)
+    }
+}
+
+/// Split `text` on commas, returning each trimmed part with the offset it starts at.
+fn split_operands(text: &str, offset: usize) -> Vec<(usize, &str)> {
+    let mut parts = vec![];
+    let mut start = 0;
+
+    for (idx, part) in text.split(',').enumerate() {
+        if idx > 0 {
+            start += 1;
+        }
+        let leading = part.len() - part.trim_start().len();
+        parts.push((offset + start + leading, part.trim()));
+        start += part.len();
+    }
+
+    parts
+}
+
+/// The first whitespace separated word of `text` at or after `offset`.
+fn next_word(text: &str, offset: usize) -> Option<(usize, &str)> {
+    let rest = &text[offset..];
+    let start = offset + (rest.len() - rest.trim_start().len());
+    let word = text[start..].split_whitespace().next()?;
+
+    Some((start, word))
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(isize),
+    Label(String, usize),
+}
+
+#[derive(Debug, Clone)]
+struct Expression {
+    terms: Vec<(isize, Term)>,
+    offset: usize,
+}
+
+impl Expression {
+    fn parse(source: &SourceLine, text: &str, offset: usize) -> Result<Self, AsmError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut terms = vec![];
+        let mut idx = 0;
+        let mut sign = 1;
+        let mut expect_term = true;
+
+        while idx < chars.len() {
+            let c = chars[idx];
+
+            if c.is_whitespace() {
+                idx += 1;
+            } else if expect_term && (c == '+' || c == '-') {
+                if c == '-' {
+                    sign = -sign;
+                }
+                idx += 1;
+            } else if !expect_term && (c == '+' || c == '-') {
+                sign = if c == '-' { -1 } else { 1 };
+                expect_term = true;
+                idx += 1;
+            } else if expect_term && c.is_ascii_digit() {
+                let start = idx;
+                while idx < chars.len() && chars[idx].is_ascii_digit() {
+                    idx += 1;
+                }
+                let digits: String = chars[start..idx].iter().collect();
+                // Parse with the sign attached, so `-9223372036854775808` fits
+                let signed = if sign < 0 {
+                    format!("-{}", digits)
+                } else {
+                    digits
+                };
+                let value = signed.parse::<isize>().map_err(|_| {
+                    source.error(offset + start, format!("Number `{}` is too large", signed))
+                })?;
+
+                terms.push((1, Term::Number(value)));
+                sign = 1;
+                expect_term = false;
+            } else if expect_term && is_identifier_start(c) {
+                let start = idx;
+                while idx < chars.len() && is_identifier(chars[idx]) {
+                    idx += 1;
+                }
+                let name: String = chars[start..idx].iter().collect();
+
+                terms.push((sign, Term::Label(name, offset + start)));
+                sign = 1;
+                expect_term = false;
+            } else {
+                return Err(source.error(offset + idx, format!("Unexpected `{}`", c)));
+            }
+        }
+
+        if expect_term {
+            return Err(source.error(offset + idx, "Expected a number or label"));
+        }
+
+        Ok(Self { terms, offset })
+    }
+
+    fn evaluate(
+        &self,
+        source: &SourceLine,
+        labels: &HashMap<String, usize>,
+    ) -> Result<isize, AsmError> {
+        self.terms.iter().try_fold(0isize, |total, (sign, term)| {
+            let value = match term {
+                Term::Number(value) => *value,
+                Term::Label(name, offset) => match labels.get(name) {
+                    Some(&address) => address as isize,
+                    None => {
+                        return Err(source.error(*offset, format!("Undefined label `{}`", name)))
+                    }
+                },
+            };
+
+            sign.checked_mul(value)
+                .and_then(|value| total.checked_add(value))
+                .ok_or_else(|| source.error(self.offset, "Expression overflows a word"))
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Immediate(Expression),
+    Position(Expression),
+    Relative(Expression),
+}
+
+impl Operand {
+    fn parse(source: &SourceLine, text: &str, offset: usize) -> Result<Self, AsmError> {
+        if let Some(rest) = text.strip_prefix('#') {
+            return Ok(Operand::Immediate(Expression::parse(
+                source,
+                rest,
+                offset + 1,
+            )?));
+        }
+
+        if text.starts_with('[') {
+            if !text.ends_with(']') {
+                return Err(source.error(offset + text.len(), "Expected `]`"));
+            }
+
+            let inner = &text[1..text.len() - 1];
+            let leading = inner.len() - inner.trim_start().len();
+            let trimmed = inner.trim_start();
+            let is_relative = trimmed.starts_with("rb") && !trimmed[2..].starts_with(is_identifier);
+
+            return if is_relative {
+                let rest = &trimmed[2..];
+                if rest.trim().is_empty() {
+                    Ok(Operand::Relative(Expression {
+                        terms: vec![(1, Term::Number(0))],
+                        offset: offset + 1 + leading,
+                    }))
+                } else {
+                    let rest_offset = offset + 1 + leading + 2;
+                    if !rest.trim_start().starts_with(['+', '-']) {
+                        return Err(source.error(rest_offset, "Expected `+` or `-` after `rb`"));
+                    }
+
+                    Ok(Operand::Relative(Expression::parse(
+                        source,
+                        rest,
+                        rest_offset,
+                    )?))
+                }
+            } else {
+                Ok(Operand::Position(Expression::parse(
+                    source,
+                    inner,
+                    offset + 1,
+                )?))
+            };
+        }
+
+        Err(source.error(
+            offset,
+            format!(
+                "Expected an operand like `#1`, `[1]` or `[rb+1]`, found `{}`",
+                text
+            ),
+        ))
+    }
+
+    fn mode(&self) -> isize {
+        match self {
+            Operand::Position(_) => 0,
+            Operand::Immediate(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+
+    fn expression(&self) -> &Expression {
+        match self {
+            Operand::Immediate(expression)
+            | Operand::Position(expression)
+            | Operand::Relative(expression) => expression,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    Instruction(isize, Vec<Operand>),
+    Data(Vec<Expression>),
+}
+
+impl Statement {
+    fn length(&self) -> usize {
+        match self {
+            Statement::Instruction(_, operands) => 1 + operands.len(),
+            Statement::Data(values) => values.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParsedLine {
+    source: SourceLine,
+    labels: Vec<(String, usize)>,
+    address: Option<(usize, usize)>,
+    statement: Option<Statement>,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<SourceLine>,
+}
+
+/// Replace whole identifiers in `text` according to `substitutions`.
+fn substitute(text: &str, substitutions: &HashMap<&str, &str>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut identifier = String::new();
+
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if is_identifier(c) && (!identifier.is_empty() || is_identifier_start(c)) {
+            identifier.push(c);
+            continue;
+        }
+
+        if !identifier.is_empty() {
+            result.push_str(
+                substitutions
+                    .get(identifier.as_str())
+                    .unwrap_or(&&*identifier),
+            );
+            identifier.clear();
+        }
+
+        if c != '\n' {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Split a line into its leading `label:` definitions and the rest of the line.
+fn split_labels(text: &str) -> (Vec<(usize, &str)>, usize) {
+    let mut labels = vec![];
+    let mut offset = 0;
+
+    while let Some((start, word)) = next_word(text, offset) {
+        match word.find(':') {
+            Some(colon) if colon > 0 => {
+                labels.push((start, &word[..colon]));
+                offset = start + colon + 1;
+            }
+            _ => break,
+        }
+    }
+
+    (labels, offset)
+}
+
+fn expand_macros(
+    lines: Vec<SourceLine>,
+    macros: &mut HashMap<String, Macro>,
+    depth: usize,
+) -> Result<Vec<SourceLine>, AsmError> {
+    let mut expanded = vec![];
+    let mut lines = lines.into_iter();
+
+    while let Some(source) = lines.next() {
+        let (labels, offset) = split_labels(&source.text);
+        let (word_offset, word) = match next_word(&source.text, offset) {
+            Some(word) => word,
+            None => {
+                expanded.push(source);
+                continue;
+            }
+        };
+
+        if word == ".macro" {
+            let definition = &source.text[word_offset + word.len()..];
+            let (name_offset, name) = next_word(definition, 0)
+                .ok_or_else(|| source.error(word_offset, "Expected a macro name"))?;
+            let parameters: Vec<String> = definition[name_offset + name.len()..]
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(String::from)
+                .collect();
+            let mut body = vec![];
+
+            loop {
+                match lines.next() {
+                    Some(line) if line.text.trim() == ".endm" => break,
+                    Some(line) => body.push(line),
+                    None => return Err(source.error(word_offset, "Missing `.endm` for `.macro`")),
+                }
+            }
+
+            macros.insert(name.to_string(), Macro { parameters, body });
+            continue;
+        }
+
+        let (parameters, body) = match macros.get(word) {
+            Some(definition) => (definition.parameters.clone(), definition.body.clone()),
+            None => {
+                expanded.push(source);
+                continue;
+            }
+        };
+
+        if depth >= MAX_MACRO_DEPTH {
+            return Err(source.error(word_offset, "Macros are nested too deeply"));
+        }
+
+        let arguments_offset = word_offset + word.len();
+        let arguments: Vec<_> = split_operands(&source.text[arguments_offset..], arguments_offset)
+            .into_iter()
+            .filter(|(_, argument)| !argument.is_empty())
+            .collect();
+        if arguments.len() != parameters.len() {
+            return Err(source.error(
+                word_offset,
+                format!(
+                    "Macro `{}` takes {} arguments, {} given",
+                    word,
+                    parameters.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        if !labels.is_empty() {
+            expanded.push(SourceLine {
+                line: source.line,
+                text: source.text[..offset].to_string(),
+            });
+        }
+
+        let substitutions: HashMap<&str, &str> = parameters
+            .iter()
+            .map(String::as_str)
+            .zip(arguments.iter().map(|&(_, argument)| argument))
+            .collect();
+        let body = body
+            .iter()
+            .map(|line| SourceLine {
+                line: line.line,
+                text: substitute(&line.text, &substitutions),
+            })
+            .collect();
+
+        expanded.extend(expand_macros(body, macros, depth + 1)?);
+    }
+
+    Ok(expanded)
+}
+
+fn parse_line(source: SourceLine) -> Result<ParsedLine, AsmError> {
+    let (raw_labels, offset) = split_labels(&source.text);
+    let mut labels = vec![];
+    let mut address = None;
+
+    for (label_offset, label) in raw_labels {
+        if label.chars().all(|c| c.is_ascii_digit()) {
+            let value = label
+                .parse()
+                .map_err(|_| source.error(label_offset, "Address is too large"))?;
+            address = Some((value, label_offset));
+        } else if label.starts_with(is_identifier_start) && label.chars().all(is_identifier) {
+            labels.push((label.to_string(), label_offset));
+        } else {
+            return Err(source.error(label_offset, format!("Invalid label `{}`", label)));
+        }
+    }
+
+    let statement = match next_word(&source.text, offset) {
+        None => None,
+        Some((word_offset, word)) => {
+            let operands_offset = word_offset + word.len();
+            let operands: Vec<_> = split_operands(&source.text[operands_offset..], operands_offset)
+                .into_iter()
+                .filter(|(_, operand)| !operand.is_empty())
+                .collect();
+
+            if word == ".data" {
+                if operands.is_empty() {
+                    return Err(source.error(word_offset, "Expected at least one value"));
+                }
+
+                let values = operands
+                    .iter()
+                    .map(|&(operand_offset, operand)| {
+                        Expression::parse(&source, operand, operand_offset)
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Some(Statement::Data(values))
+            } else {
+                let (opcode, parameter_count, write) = mnemonic(word).ok_or_else(|| {
+                    source.error(word_offset, format!("Unknown instruction `{}`", word))
+                })?;
+
+                if operands.len() != parameter_count {
+                    return Err(source.error(
+                        word_offset,
+                        format!(
+                            "`{}` takes {} operands, {} given",
+                            word,
+                            parameter_count,
+                            operands.len()
+                        ),
+                    ));
+                }
+
+                let operands = operands
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &(operand_offset, operand))| {
+                        let parsed = Operand::parse(&source, operand, operand_offset)?;
+
+                        match parsed {
+                            Operand::Immediate(_) if write == Some(idx) => Err(source.error(
+                                operand_offset,
+                                format!("`{}` can't write to an immediate operand", word),
+                            )),
+                            _ => Ok(parsed),
+                        }
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Some(Statement::Instruction(opcode, operands))
+            }
+        }
+    };
+
+    Ok(ParsedLine {
+        source,
+        labels,
+        address,
+        statement,
+    })
+}
+
+/// Assemble `source` into a program that can be loaded by
+/// [`Computer::new`](crate::intcode_computer::Computer::new).
+pub fn assemble(source: &str) -> Result<Vec<isize>, AsmError> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(idx, text)| SourceLine {
+            line: idx + 1,
+            text: text.split(';').next().unwrap_or("").to_string(),
+        })
+        .collect();
+    let expanded = expand_macros(lines, &mut HashMap::new(), 0)?;
+    let parsed = expanded
+        .into_iter()
+        .map(parse_line)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // First pass, find the address of every label
+    let mut labels = HashMap::new();
+    let mut address = 0;
+    for line in &parsed {
+        if let Some((expected, offset)) = line.address {
+            if expected != address {
+                return Err(line.source.error(
+                    offset,
+                    format!(
+                        "Expected address {}, but this line is at {}",
+                        expected, address
+                    ),
+                ));
+            }
+        }
+
+        for (label, offset) in &line.labels {
+            if labels.insert(label.clone(), address).is_some() {
+                return Err(line
+                    .source
+                    .error(*offset, format!("Label `{}` is already defined", label)));
+            }
+        }
+
+        address += line.statement.as_ref().map_or(0, Statement::length);
+    }
+
+    // Second pass, encode
+    let mut program = Vec::with_capacity(address);
+    for line in &parsed {
+        match &line.statement {
+            None => (),
+            Some(Statement::Data(values)) => {
+                for value in values {
+                    program.push(value.evaluate(&line.source, &labels)?);
+                }
+            }
+            Some(Statement::Instruction(opcode, operands)) => {
+                let modes = operands
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, operand)| operand.mode() * 10_isize.pow(idx as u32 + 2))
+                    .sum::<isize>();
+                program.push(opcode + modes);
+
+                for operand in operands {
+                    program.push(operand.expression().evaluate(&line.source, &labels)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assemble, AsmError};
+    use crate::intcode::disasm::disassemble;
+    use crate::intcode_computer::Computer;
+    use crate::{load_file, parse_custom_separated};
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; Echo input until a zero is read
+            loop:   in [value]
+                    jz [value], #done
+                    out [value]
+                    jnz #1, #loop
+            done:   hlt
+            value:  .data 0
+        ";
+        let program = assemble(source).unwrap();
+
+        assert_eq!(program, vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+
+        let mut computer = Computer::new(program);
+        computer.push_inputs(vec![4, 2, 0]);
+        computer.run_until_halt_or_paused(false);
+
+        assert_eq!(computer.all_outputs(), &[4, 2]);
+    }
+
+    #[test]
+    fn test_relative_operands_and_label_offsets() {
+        assert_eq!(
+            assemble("arb #-3\nadd [rb+2], [rb-1], [rb]\nout [end+1]\nend: .data end, end - 2")
+                .unwrap(),
+            vec![109, -3, 22201, 2, -1, 0, 4, 9, 8, 6]
+        );
+    }
+
+    #[test]
+    fn test_macros() {
+        let source = "
+.macro push value
+    add value, #0, [rb+0]
+    arb #1
+.endm
+.macro push_pair a, b
+    push a
+    push b
+.endm
+start: push_pair #5, [start]
+    hlt
+";
+
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![21101, 5, 0, 0, 109, 1, 21001, 0, 0, 0, 109, 1, 99]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("hlt\n  foo [1]"),
+            Err(AsmError::new(2, 3, "Unknown instruction `foo`"))
+        );
+        assert_eq!(
+            assemble("add #1, #2, #3"),
+            Err(AsmError::new(
+                1,
+                13,
+                "`add` can't write to an immediate operand"
+            ))
+        );
+        assert_eq!(
+            assemble("jnz #1, #nowhere"),
+            Err(AsmError::new(1, 10, "Undefined label `nowhere`"))
+        );
+        assert_eq!(
+            assemble("out 1"),
+            Err(AsmError::new(
+                1,
+                5,
+                "Expected an operand like `#1`, `[1]` or `[rb+1]`, found `1`"
+            ))
+        );
+        assert_eq!(
+            assemble("hlt\n0002: hlt"),
+            Err(AsmError::new(
+                2,
+                1,
+                "Expected address 2, but this line is at 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        for path in &["day09.txt", "sum-of-primes.in"] {
+            let input = load_file(path);
+            let program: Vec<isize> = parse_custom_separated(&input, ",").collect();
+            let listing = disassemble(&program).to_string();
+
+            assert_eq!(assemble(&listing).unwrap(), program, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_word_limits() {
+        let program = vec![isize::MIN, isize::MAX, -1];
+        let listing = disassemble(&program).to_string();
+        assert_eq!(assemble(&listing).unwrap(), program);
+
+        assert_eq!(
+            assemble(".data 9223372036854775807 + 1"),
+            Err(AsmError::new(1, 7, "Expression overflows a word"))
+        );
+        assert_eq!(
+            assemble(".data -9223372036854775808 - 1"),
+            Err(AsmError::new(1, 7, "Expression overflows a word"))
+        );
+        assert_eq!(
+            assemble(".data 9223372036854775808"),
+            Err(AsmError::new(
+                1,
+                7,
+                "Number `9223372036854775808` is too large"
+            ))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Control flow graphs of Intcode programs.
+//!
+//! [`control_flow_graph`] connects the [`basic_blocks`] of a program with the edges that can be
+//! found without running it. Conditional jumps to immediate targets are static edges, jumps to any
+//! other target are dynamic and listed separately, as are writes into the program's own code. The
+//! graph can be rendered with Graphviz through [`ControlFlowGraph::to_dot`]:
+//!
+//! ```text
+//! cargo run --bin intcode-cfg day15.txt | dot -Tsvg > day15.svg
+//! ```
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use crate::intcode::compile::{basic_blocks, BasicBlock};
+use crate::intcode::disasm::{static_successors, static_write};
+use crate::intcode_computer::{Instruction, Parameter};
+
+/// How control reaches the block an [`Edge`] leads to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EdgeKind {
+    /// Control continues after the last instruction of the block.
+    FallThrough,
+    /// A conditional jump with an immediate target is taken.
+    Jump,
+}
+
+/// A static edge between two blocks, identified by their start addresses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A write by the instruction at `address` into `target`, a word of a reachable instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeWrite {
+    pub address: usize,
+    pub target: usize,
+}
+
+/// The statically known control flow of a program, see [`control_flow_graph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    /// Blocks ordered by address.
+    pub blocks: Vec<BasicBlock>,
+    /// Edges ordered by the block they leave.
+    pub edges: Vec<Edge>,
+    /// Addresses of jumps whose target is only known at runtime, like returns from functions.
+    pub dynamic_jumps: Vec<usize>,
+    /// Position mode writes into code, ordered by the address of the writing instruction.
+    ///
+    /// Writes through relative mode parameters can't be resolved statically and are missing.
+    pub code_writes: Vec<CodeWrite>,
+}
+
+/// Whether `address` is a jump whose target isn't a constant.
+fn is_dynamic_jump(address: usize, instruction: &Instruction, written: &HashSet<usize>) -> bool {
+    match *instruction {
+        Instruction::ConditionalJump(_, _, Parameter::Immediate(target)) => {
+            target < 0 || written.contains(&(address + 2))
+        }
+        Instruction::ConditionalJump(_, _, _) => true,
+        _ => false,
+    }
+}
+
+/// Find the basic blocks of `program` and the edges between them.
+pub fn control_flow_graph(program: &[isize]) -> ControlFlowGraph {
+    let blocks = basic_blocks(program);
+    let instructions = || blocks.iter().flat_map(|block| &block.instructions);
+
+    let written: HashSet<_> = instructions()
+        .filter_map(|(_, instruction)| static_write(instruction))
+        .collect();
+    let code: HashSet<_> = instructions()
+        .flat_map(|(address, instruction)| *address..address + instruction.length())
+        .collect();
+    let starts: HashSet<_> = blocks.iter().map(|block| block.start).collect();
+
+    let mut edges = BTreeSet::new();
+    let mut dynamic_jumps = vec![];
+    for block in &blocks {
+        let (address, instruction) = match block.instructions.last() {
+            Some(last) => last,
+            None => continue,
+        };
+
+        for to in static_successors(*address, instruction, &written) {
+            let kind = if to == block.end() {
+                EdgeKind::FallThrough
+            } else {
+                EdgeKind::Jump
+            };
+
+            if starts.contains(&to) {
+                edges.insert(Edge {
+                    from: block.start,
+                    to,
+                    kind,
+                });
+            }
+        }
+
+        if is_dynamic_jump(*address, instruction, &written) {
+            dynamic_jumps.push(*address);
+        }
+    }
+
+    let code_writes = instructions()
+        .filter_map(|(address, instruction)| {
+            static_write(instruction)
+                .filter(|target| code.contains(target))
+                .map(|target| CodeWrite {
+                    address: *address,
+                    target,
+                })
+        })
+        .collect();
+
+    ControlFlowGraph {
+        blocks,
+        edges: edges.into_iter().collect(),
+        dynamic_jumps,
+        code_writes,
+    }
+}
+
+impl ControlFlowGraph {
+    /// The block containing the instruction at `address`.
+    pub fn block_containing(&self, address: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .iter()
+            .find(|block| block.start <= address && address < block.end())
+    }
+
+    /// Render the graph in the Graphviz dot language.
+    ///
+    /// Blocks list their instructions, jumps are drawn bold, dynamic jumps lead to a shared
+    /// `dynamic` node and writes into code are dashed red edges to the block that's written.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph intcode {{");
+        let _ = writeln!(out, "    node [shape=box, fontname=\"monospace\"];");
+
+        for block in &self.blocks {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|(address, instruction)| format!("{:04}: {}\\l", address, instruction))
+                .collect();
+            let _ = writeln!(out, "    b{} [label=\"{}\"];", block.start, label);
+        }
+
+        if !self.dynamic_jumps.is_empty() {
+            let _ = writeln!(out, "    dynamic [shape=ellipse, style=dashed];");
+        }
+
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::FallThrough => "",
+                EdgeKind::Jump => " [style=bold]",
+            };
+            let _ = writeln!(out, "    b{} -> b{}{};", edge.from, edge.to, style);
+        }
+
+        for &address in &self.dynamic_jumps {
+            if let Some(block) = self.block_containing(address) {
+                let _ = writeln!(out, "    b{} -> dynamic [style=dashed];", block.start);
+            }
+        }
+
+        for write in &self.code_writes {
+            if let (Some(from), Some(to)) = (
+                self.block_containing(write.address),
+                self.block_containing(write.target),
+            ) {
+                let _ = writeln!(
+                    out,
+                    "    b{} -> b{} [style=dashed, color=red, label=\"writes {}\"];",
+                    from.start, to.start, write.target
+                );
+            }
+        }
+
+        let _ = writeln!(out, "}}");
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{control_flow_graph, CodeWrite, Edge, EdgeKind};
+
+    #[test]
+    fn test_edges() {
+        // Counts down from the input, outputting every value
+        let program = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+        let cfg = control_flow_graph(&program);
+
+        assert_eq!(
+            cfg.edges,
+            vec![
+                Edge {
+                    from: 0,
+                    to: 2,
+                    kind: EdgeKind::FallThrough
+                },
+                Edge {
+                    from: 2,
+                    to: 2,
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    from: 2,
+                    to: 11,
+                    kind: EdgeKind::FallThrough
+                },
+            ]
+        );
+        assert!(cfg.dynamic_jumps.is_empty());
+        assert!(cfg.code_writes.is_empty());
+        assert_eq!(cfg.block_containing(6).map(|block| block.start), Some(2));
+
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph intcode {\n    \
+                 node [shape=box, fontname=\"monospace\"];\n    \
+                 b0 [label=\"0000: in [12]\\l\"];\n    \
+                 b2 [label=\"0002: out [12]\\l0004: add [12], #-1, [12]\\l0008: jnz [12], #2\\l\"];\n    \
+                 b11 [label=\"0011: hlt\\l\"];\n    \
+                 b0 -> b2;\n    \
+                 b2 -> b2 [style=bold];\n    \
+                 b2 -> b11;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_jumps_and_code_writes() {
+        // Jumps to the address stored on the stack
+        let cfg = control_flow_graph(&[109, 10, 2105, 1, 0, 99]);
+        assert_eq!(cfg.dynamic_jumps, vec![2]);
+        assert!(cfg.edges.is_empty());
+        assert!(cfg.to_dot().contains("    b0 -> dynamic [style=dashed];\n"));
+
+        // The condition of the jump at 6 is overwritten by the comparison at 2
+        let cfg =
+            control_flow_graph(&[3, 20, 1007, 20, 2, 7, 1105, -1, 12, 104, 1, 99, 104, 2, 99]);
+        assert_eq!(
+            cfg.code_writes,
+            vec![CodeWrite {
+                address: 2,
+                target: 7
+            }]
+        );
+        assert!(cfg
+            .to_dot()
+            .contains("    b0 -> b0 [style=dashed, color=red, label=\"writes 7\"];\n"));
+        assert!(cfg.dynamic_jumps.is_empty());
+    }
+}
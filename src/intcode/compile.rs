@@ -0,0 +1,1226 @@
+//! Compiling Intcode programs instead of interpreting them.
+//!
+//! [`basic_blocks`] splits the statically reachable part of a program into basic blocks and
+//! [`emit_rust`] turns those into a standalone Rust module. [`Backend::Compiled`] finds blocks
+//! while the program runs instead, and compiles each one to a list of closures the first time it's
+//! reached.
+//!
+//! Both fall back to interpretation when a program modifies its own code. Generated Rust returns
+//! [`Exit::Fallback`](emit_rust) so the caller can continue in a [`Computer`], while compiled
+//! blocks are recompiled with the modified words read from memory every time they're used.
+//! Instructions whose opcode is modified are always interpreted.
+//!
+//! [`Backend::Compiled`]: crate::intcode_computer::Backend::Compiled
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use crate::intcode::disasm::{code_pointer, reachable_instructions};
+use crate::intcode_computer::{
+    adjusted_relative_base, to_address, ComparisonOp, Computer, ExecutionState, Instruction,
+    IntcodeError, IntcodeErrorReason, JumpCondition, Memory, Op, Overflow, Parameter, Word,
+};
+
+/// Blocks are only compiled below this address, like decoded instructions are only cached below
+/// it, execution above it is interpreted.
+const COMPILE_LIMIT: usize = 1 << 16;
+/// Longer runs of straight line code are split into several blocks.
+const MAX_BLOCK_LENGTH: usize = 256;
+
+/// A run of instructions that control only enters at the first instruction and only leaves after
+/// the last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    /// Instructions with their addresses, in execution order.
+    pub instructions: Vec<(usize, Instruction)>,
+}
+
+impl BasicBlock {
+    /// The address following the last instruction of the block.
+    pub fn end(&self) -> usize {
+        self.instructions
+            .last()
+            .map_or(self.start, |(address, instruction)| {
+                address + instruction.length()
+            })
+    }
+}
+
+fn ends_block<W: Word>(instruction: &Instruction<W>) -> bool {
+    matches!(
+        instruction,
+        Instruction::ConditionalJump(_, _, _) | Instruction::Halt
+    )
+}
+
+/// Split the instructions reachable from address 0, as found by the disassembler, into basic
+/// blocks ordered by address.
+///
+/// Blocks start at address 0, at immediate jump targets, after jumps, at inputs so a machine
+/// waiting for one can resume there, and at addresses that look like code pointers stored by the
+/// program. Jumps to any other address can only be resolved at runtime.
+pub fn basic_blocks(program: &[isize]) -> Vec<BasicBlock> {
+    let instructions = reachable_instructions(program);
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+
+    for (&address, instruction) in &instructions {
+        if let Instruction::ConditionalJump(_, _, target) = instruction {
+            leaders.insert(address + instruction.length());
+
+            if let Parameter::Immediate(target) = target {
+                if *target >= 0 {
+                    leaders.insert(*target as usize);
+                }
+            }
+        }
+
+        if let Instruction::Input(_) = instruction {
+            leaders.insert(address);
+        }
+
+        leaders.extend(code_pointer(instruction));
+    }
+
+    let mut blocks: Vec<BasicBlock> = vec![];
+    for (&address, &instruction) in &instructions {
+        let continues_block = blocks.last().is_some_and(|block| {
+            block.end() == address
+                && !leaders.contains(&address)
+                && !block
+                    .instructions
+                    .last()
+                    .is_some_and(|(_, last)| ends_block(last))
+        });
+
+        if continues_block {
+            blocks
+                .last_mut()
+                .unwrap()
+                .instructions
+                .push((address, instruction));
+        } else {
+            blocks.push(BasicBlock {
+                start: address,
+                instructions: vec![(address, instruction)],
+            });
+        }
+    }
+
+    blocks
+}
+
+fn rust_value(parameter: &Parameter, address: usize) -> String {
+    match *parameter {
+        Parameter::Immediate(value) => value.to_string(),
+        Parameter::Position(position) => format!("self.read({})", position),
+        Parameter::Relative(offset) => {
+            format!("self.read(self.relative({}, {})?)", offset, address)
+        }
+    }
+}
+
+fn rust_address(parameter: &Parameter, address: usize) -> String {
+    match *parameter {
+        Parameter::Position(position) => position.to_string(),
+        Parameter::Relative(offset) => format!("self.relative({}, {})?", offset, address),
+        Parameter::Immediate(_) => unreachable!("Immediate writes don't decode"),
+    }
+}
+
+/// Ranges of consecutive addresses, as Rust range patterns joined by `|`.
+fn rust_ranges(addresses: &BTreeSet<usize>) -> String {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+
+    for &address in addresses {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == address => *end = address,
+            _ => ranges.push((address, address)),
+        }
+    }
+
+    if ranges.is_empty() {
+        return "_ if false".to_string();
+    }
+
+    ranges
+        .iter()
+        .map(|&(start, end)| format!("{}..={}", start, end))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn emit_instruction(out: &mut String, address: usize, instruction: &Instruction) {
+    let next = address + instruction.length();
+    let indent = " ".repeat(16);
+    let target = |out: &mut String, target: &Parameter| {
+        let _ = writeln!(
+            out,
+            "{}let address = {};",
+            indent,
+            rust_address(target, address)
+        );
+    };
+    let store = |out: &mut String, value: &str| {
+        let _ = writeln!(
+            out,
+            "{}if self.write(address, {}) {{\n{}    self.ip = {};\n{}    return Ok(Exit::Fallback);\n{}}}",
+            indent, value, indent, next, indent, indent
+        );
+    };
+
+    let _ = writeln!(out, "{}// {:04}: {}", indent, address, instruction);
+
+    match instruction {
+        Instruction::Op(op, a1, a2, a3) => {
+            // Like the interpreter, which wraps unless asked to trap
+            let method = match op {
+                Op::Add => "wrapping_add",
+                Op::Multiply => "wrapping_mul",
+            };
+            let value = format!(
+                "isize::{}({}, {})",
+                method,
+                rust_value(a1, address),
+                rust_value(a2, address)
+            );
+            let _ = writeln!(out, "{}let value = {};", indent, value);
+            target(out, a3);
+            store(out, "value");
+        }
+        Instruction::Compare(op, a1, a2, a3) => {
+            let symbol = match op {
+                ComparisonOp::LessThan => "<",
+                ComparisonOp::Equal => "==",
+            };
+            let value = format!(
+                "({} {} {}) as isize",
+                rust_value(a1, address),
+                symbol,
+                rust_value(a2, address)
+            );
+            let _ = writeln!(out, "{}let value = {};", indent, value);
+            target(out, a3);
+            store(out, "value");
+        }
+        Instruction::Input(a1) => {
+            target(out, a1);
+            let _ = writeln!(out, "{}let value = self.input({})?;", indent, address);
+            store(out, "value");
+        }
+        Instruction::Output(a1) => {
+            let _ = writeln!(
+                out,
+                "{}self.outputs.push({});",
+                indent,
+                rust_value(a1, address)
+            );
+        }
+        Instruction::RelativeBaseAdjust(a1) => {
+            let _ = writeln!(
+                out,
+                "{}self.adjust_relative_base({}, {})?;",
+                indent,
+                rust_value(a1, address),
+                address
+            );
+        }
+        Instruction::ConditionalJump(condition, a1, a2) => {
+            let comparison = match condition {
+                JumpCondition::IfTrue => "!=",
+                JumpCondition::IfFalse => "==",
+            };
+            let _ = writeln!(
+                out,
+                "{}let test = {};\n{}let target = {};",
+                indent,
+                rust_value(a1, address),
+                indent,
+                rust_value(a2, address)
+            );
+            let _ = writeln!(
+                out,
+                "{}if test {} 0 {{\n{}    self.ip = Self::target(target, {})?;\n{}    continue;\n{}}}",
+                indent, comparison, indent, address, indent, indent
+            );
+        }
+        Instruction::Halt => {
+            let _ = writeln!(
+                out,
+                "{}self.ip = {};\n{}return Ok(Exit::Halted);",
+                indent, address, indent
+            );
+        }
+        Instruction::Extended(_) => unreachable!("Blocks only contain standard instructions"),
+    }
+}
+
+const RUST_PRELUDE: &str = "\
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Halted,
+    /// `run` continues where it left off once more input is queued.
+    NeedsInput,
+    /// The program modified its own code or jumped to code that wasn't compiled, an interpreter
+    /// should continue from the machine's state.
+    Fallback,
+    /// The instruction at the address accessed a negative address or overflowed the relative base.
+    Fault(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    pub memory: Vec<isize>,
+    /// Words written past `DENSE_WORDS`.
+    pub sparse: BTreeMap<usize, isize>,
+    pub ip: usize,
+    pub relative_base_offset: isize,
+    pub inputs: VecDeque<isize>,
+    pub outputs: Vec<isize>,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Self {
+            memory: PROGRAM.to_vec(),
+            sparse: BTreeMap::new(),
+            ip: 0,
+            relative_base_offset: 0,
+            inputs: VecDeque::new(),
+            outputs: vec![],
+        }
+    }
+
+    /// Run until the program halts, needs input, faults or leaves the compiled code.
+    pub fn run(&mut self) -> Exit {
+        match self.execute() {
+            Ok(exit) | Err(exit) => {
+                if let Exit::Fault(ip) = exit {
+                    self.ip = ip;
+                }
+
+                exit
+            }
+        }
+    }
+
+    fn read(&self, address: usize) -> isize {
+        match self.memory.get(address) {
+            Some(&word) => word,
+            None => self.sparse.get(&address).copied().unwrap_or(0),
+        }
+    }
+
+    /// Returns whether the write modified compiled code.
+    fn write(&mut self, address: usize, value: isize) -> bool {
+        if self.read(address) == value {
+            return false;
+        }
+
+        if address < DENSE_WORDS {
+            if address >= self.memory.len() {
+                self.memory.resize(address + 1, 0);
+            }
+            self.memory[address] = value;
+        } else {
+            self.sparse.insert(address, value);
+        }
+
+        is_code(address)
+    }
+
+    #[allow(dead_code)]
+    fn relative(&self, offset: isize, ip: usize) -> Result<usize, Exit> {
+        match self.relative_base_offset.checked_add(offset) {
+            Some(address) if address >= 0 => Ok(address as usize),
+            _ => Err(Exit::Fault(ip)),
+        }
+    }
+
+    #[allow(dead_code)]
+    fn adjust_relative_base(&mut self, adjustment: isize, ip: usize) -> Result<(), Exit> {
+        self.relative_base_offset = self
+            .relative_base_offset
+            .checked_add(adjustment)
+            .ok_or(Exit::Fault(ip))?;
+
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn input(&mut self, ip: usize) -> Result<isize, Exit> {
+        self.inputs.pop_front().ok_or_else(|| {
+            self.ip = ip;
+            Exit::NeedsInput
+        })
+    }
+
+    #[allow(dead_code)]
+    fn target(target: isize, ip: usize) -> Result<usize, Exit> {
+        if target < 0 {
+            Err(Exit::Fault(ip))
+        } else {
+            Ok(target as usize)
+        }
+    }
+";
+
+/// Translate the reachable part of `program` into the source of a standalone Rust module.
+///
+/// The module exports a `Machine` holding the program's state, whose `run` method executes the
+/// compiled basic blocks until the program halts, needs input or faults. Outputs are collected
+/// without stopping. When the program writes to its own code, or jumps to an address that isn't
+/// the start of a compiled block, `run` returns `Exit::Fallback` and the machine's public state
+/// can be loaded into a [`Computer`] to continue.
+pub fn emit_rust(program: &[isize]) -> String {
+    let blocks = basic_blocks(program);
+    let code: BTreeSet<usize> = blocks
+        .iter()
+        .flat_map(|block| block.start..block.end())
+        .collect();
+    let words: Vec<_> = program.iter().map(ToString::to_string).collect();
+    let mut out = String::new();
+
+    let _ = writeln!(out, "// Generated by `intcode::compile::emit_rust`.");
+    let _ = writeln!(out, "use std::collections::{{BTreeMap, VecDeque}};\n");
+    let _ = writeln!(
+        out,
+        "const PROGRAM: [isize; {}] = [{}];",
+        words.len(),
+        words.join(", ")
+    );
+    let _ = writeln!(
+        out,
+        "/// Memory below this address is kept in a vector.\nconst DENSE_WORDS: usize = 1 << 20;\n"
+    );
+    let _ = writeln!(
+        out,
+        "fn is_code(address: usize) -> bool {{\n    matches!(address, {})\n}}\n",
+        rust_ranges(&code)
+    );
+    out.push_str(RUST_PRELUDE);
+
+    let _ = writeln!(
+        out,
+        "\n    fn execute(&mut self) -> Result<Exit, Exit> {{\n        loop {{\n            match self.ip {{"
+    );
+    for block in &blocks {
+        let _ = writeln!(out, "            {} => {{", block.start);
+
+        for (address, instruction) in &block.instructions {
+            emit_instruction(&mut out, *address, instruction);
+        }
+
+        if block
+            .instructions
+            .last()
+            .is_some_and(|(_, last)| *last != Instruction::Halt)
+        {
+            let _ = writeln!(out, "                self.ip = {};", block.end());
+        }
+        let _ = writeln!(out, "            }}");
+    }
+    let _ = writeln!(
+        out,
+        "            _ => return Ok(Exit::Fallback),\n            }}\n        }}\n    }}\n}}"
+    );
+
+    out
+}
+
+/// What a compiled instruction wants to happen next.
+pub(crate) enum Flow<W> {
+    Next,
+    Jump(usize),
+    Output(W),
+    NeedsInput,
+    Halt,
+    /// The instruction wrote to compiled code, which has to be recompiled.
+    Modified,
+}
+
+type Step<M> =
+    Box<dyn Fn(&mut Computer<M>) -> Result<Flow<<M as Memory>::Word>, IntcodeError> + Send + Sync>;
+
+struct Block<M: Memory> {
+    steps: Vec<Step<M>>,
+    /// Address of the instruction behind each step.
+    addresses: Vec<usize>,
+    end: usize,
+}
+
+/// Blocks compiled by [`Backend::Compiled`](crate::intcode_computer::Backend::Compiled), keyed
+/// by the address they start at.
+pub(crate) struct Threaded<M: Memory> {
+    blocks: Vec<Option<Block<M>>>,
+    /// Words written after they were compiled, from then on they are read every time they're used.
+    volatile: HashSet<usize>,
+}
+
+impl<M: Memory> Default for Threaded<M> {
+    fn default() -> Self {
+        Self {
+            blocks: vec![],
+            volatile: HashSet::new(),
+        }
+    }
+}
+
+impl<M: Memory> Threaded<M> {
+    /// Run compiled blocks until the program stops or `limit` instructions were executed in total.
+    pub(crate) fn run(
+        &mut self,
+        computer: &mut Computer<M>,
+        stop_on_output: bool,
+        limit: u64,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        loop {
+            if computer.steps >= limit {
+                return Ok(ExecutionState::StepLimitReached);
+            }
+
+            if let Some(word) = computer.modified_code.take() {
+                self.invalidate(computer, word);
+            }
+
+            let ip = computer.ip;
+            if ip < COMPILE_LIMIT && self.blocks.get(ip).is_none_or(Option::is_none) {
+                self.compile(computer, ip);
+            }
+
+            let block = match self.blocks.get(ip) {
+                Some(Some(block)) if computer.steps + block.steps.len() as u64 <= limit => block,
+                _ => {
+                    // Nothing could be compiled here, or the block would exceed the limit.
+                    // Interpreting also reports any fault
+                    match computer.execute_next_instruction::<false, true>(stop_on_output)? {
+                        Some(state) => return Ok(state),
+                        None => continue,
+                    }
+                }
+            };
+
+            computer.ip = block.end;
+            for (index, step) in block.steps.iter().enumerate() {
+                let flow = step(computer)?;
+                if let Flow::NeedsInput = flow {
+                    computer.ip = block.addresses[index];
+                    return Ok(ExecutionState::NeedsInput);
+                }
+                computer.steps += 1;
+
+                match flow {
+                    Flow::Next => (),
+                    Flow::Jump(target) => {
+                        computer.ip = target;
+                        break;
+                    }
+                    Flow::Output(value) => {
+                        if stop_on_output {
+                            computer.ip = block.addresses[index] + 2;
+                            return Ok(ExecutionState::Output(value));
+                        }
+                    }
+                    Flow::NeedsInput => unreachable!("Waiting for input was handled above"),
+                    Flow::Halt => {
+                        computer.ip = block.addresses[index];
+                        computer.did_halt = true;
+                        return Ok(ExecutionState::Halted);
+                    }
+                    Flow::Modified => {
+                        computer.ip = block.addresses.get(index + 1).copied().unwrap_or(block.end);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Drop every compiled block after `word` was modified, it's read from memory from now on.
+    fn invalidate(&mut self, computer: &mut Computer<M>, word: usize) {
+        self.volatile.insert(word);
+        self.blocks.clear();
+        computer.code_words.clear();
+    }
+
+    fn compile(&mut self, computer: &mut Computer<M>, start: usize) {
+        let mut block = Block {
+            steps: vec![],
+            addresses: vec![],
+            end: start,
+        };
+
+        while block.steps.len() < MAX_BLOCK_LENGTH {
+            let address = block.end;
+            // Instructions with a modified opcode are left to the interpreter
+            if address >= COMPILE_LIMIT || self.volatile.contains(&address) {
+                break;
+            }
+
+            let words: Vec<_> = (address..address + 4)
+                .map(|word| computer.memory.read(word))
+                .collect();
+            let instruction = match Instruction::parse(address, &words) {
+                Ok(instruction) => instruction,
+                Err(_) => break,
+            };
+
+            let mut operands = [Operand::Immediate(M::Word::ZERO); 3];
+            for (index, parameter) in instruction.parameters().into_iter().enumerate() {
+                operands[index] = self.operand(address + index + 1, parameter);
+            }
+
+            let code_end = address + instruction.length();
+            if computer.code_words.len() < code_end {
+                computer.code_words.resize(code_end, false);
+            }
+            for word in address..code_end {
+                computer.code_words[word] = !self.volatile.contains(&word);
+            }
+
+            block
+                .steps
+                .push(compile_instruction(address, &instruction, operands));
+            block.addresses.push(address);
+            block.end = code_end;
+
+            if ends_block(&instruction) {
+                break;
+            }
+        }
+
+        if block.steps.is_empty() {
+            return;
+        }
+
+        if start >= self.blocks.len() {
+            let len = (start + 1).max(self.blocks.len() * 2);
+            self.blocks.resize_with(len.min(COMPILE_LIMIT), || None);
+        }
+        self.blocks[start] = Some(block);
+    }
+
+    fn operand(&self, word: usize, parameter: Parameter<M::Word>) -> Operand<M::Word> {
+        match parameter {
+            _ if self.volatile.contains(&word) => {
+                let mode = match parameter {
+                    Parameter::Position(_) => 0,
+                    Parameter::Immediate(_) => 1,
+                    Parameter::Relative(_) => 2,
+                };
+
+                Operand::Dynamic(word, mode)
+            }
+            Parameter::Immediate(value) => Operand::Immediate(value),
+            Parameter::Position(position) => Operand::Position(position),
+            Parameter::Relative(offset) => Operand::Relative(offset),
+        }
+    }
+}
+
+/// A parameter of an instruction being compiled.
+#[derive(Debug, Copy, Clone)]
+enum Operand<W> {
+    Immediate(W),
+    Position(usize),
+    Relative(isize),
+    /// A parameter whose word is modified by the program, with its mode.
+    Dynamic(usize, usize),
+}
+
+/// Where a compiled instruction reads a value from.
+trait Source<W: Word>: Copy + Send + Sync + 'static {
+    fn get<M: Memory<Word = W>>(self, computer: &Computer<M>) -> Result<W, IntcodeErrorReason>;
+}
+
+/// Where a compiled instruction writes its result to.
+trait Target: Copy + Send + Sync + 'static {
+    fn address<M: Memory>(self, computer: &Computer<M>) -> Result<usize, IntcodeErrorReason>;
+}
+
+#[derive(Copy, Clone)]
+struct Immediate<W>(W);
+
+#[derive(Copy, Clone)]
+struct Position(usize);
+
+#[derive(Copy, Clone)]
+struct Relative(isize);
+
+#[derive(Copy, Clone)]
+struct Dynamic(usize, usize);
+
+impl Dynamic {
+    fn parameter<M: Memory>(
+        self,
+        computer: &Computer<M>,
+    ) -> Result<Parameter<M::Word>, IntcodeErrorReason> {
+        Parameter::new(self.1, computer.memory.read(self.0))
+    }
+}
+
+impl<W: Word> Source<W> for Immediate<W> {
+    #[inline(always)]
+    fn get<M: Memory<Word = W>>(self, _: &Computer<M>) -> Result<W, IntcodeErrorReason> {
+        Ok(self.0)
+    }
+}
+
+impl<W: Word> Source<W> for Position {
+    #[inline(always)]
+    fn get<M: Memory<Word = W>>(self, computer: &Computer<M>) -> Result<W, IntcodeErrorReason> {
+        Ok(computer.memory.read(self.0))
+    }
+}
+
+impl<W: Word> Source<W> for Relative {
+    #[inline(always)]
+    fn get<M: Memory<Word = W>>(self, computer: &Computer<M>) -> Result<W, IntcodeErrorReason> {
+        Ok(computer.memory.read(self.address(computer)?))
+    }
+}
+
+impl<W: Word> Source<W> for Dynamic {
+    #[inline(always)]
+    fn get<M: Memory<Word = W>>(self, computer: &Computer<M>) -> Result<W, IntcodeErrorReason> {
+        computer.value(&self.parameter(computer)?)
+    }
+}
+
+impl Target for Position {
+    #[inline(always)]
+    fn address<M: Memory>(self, _: &Computer<M>) -> Result<usize, IntcodeErrorReason> {
+        Ok(self.0)
+    }
+}
+
+impl Target for Relative {
+    #[inline(always)]
+    fn address<M: Memory>(self, computer: &Computer<M>) -> Result<usize, IntcodeErrorReason> {
+        Parameter::<M::Word>::Relative(self.0).address(computer.relative_base_offset)
+    }
+}
+
+impl Target for Dynamic {
+    #[inline(always)]
+    fn address<M: Memory>(self, computer: &Computer<M>) -> Result<usize, IntcodeErrorReason> {
+        self.parameter(computer)?
+            .address(computer.relative_base_offset)
+    }
+}
+
+/// Expand `$body` once for every kind of source, with `$source` bound to it.
+macro_rules! with_source {
+    ($operand:expr, $source:ident => $body:expr) => {
+        match $operand {
+            Operand::Immediate(value) => {
+                let $source = Immediate(value);
+                $body
+            }
+            Operand::Position(position) => {
+                let $source = Position(position);
+                $body
+            }
+            Operand::Relative(offset) => {
+                let $source = Relative(offset);
+                $body
+            }
+            Operand::Dynamic(word, mode) => {
+                let $source = Dynamic(word, mode);
+                $body
+            }
+        }
+    };
+}
+
+/// Expand `$body` once for every kind of target, with `$target` bound to it.
+macro_rules! with_target {
+    ($operand:expr, $target:ident => $body:expr) => {
+        match $operand {
+            Operand::Position(position) => {
+                let $target = Position(position);
+                $body
+            }
+            Operand::Relative(offset) => {
+                let $target = Relative(offset);
+                $body
+            }
+            Operand::Dynamic(word, mode) => {
+                let $target = Dynamic(word, mode);
+                $body
+            }
+            Operand::Immediate(_) => unreachable!("Immediate writes don't decode"),
+        }
+    };
+}
+
+/// Unwrap `$result` or return a fault of parameter `$index` of the instruction at `$address`.
+macro_rules! attempt {
+    ($computer:ident, $address:ident, $index:expr, $result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(reason) => {
+                $computer.ip = $address;
+                return Err($computer.fault($index, reason));
+            }
+        }
+    };
+}
+
+trait Operation: Send + Sync + 'static {
+    /// The result, or `None` if it overflows while overflow is trapped.
+    fn apply<W: Word>(a: W, b: W, overflow: Overflow) -> Option<W>;
+}
+
+struct Add;
+struct Multiply;
+struct LessThan;
+struct Equal;
+
+impl Operation for Add {
+    #[inline(always)]
+    fn apply<W: Word>(a: W, b: W, overflow: Overflow) -> Option<W> {
+        Op::Add.apply(a, b, overflow)
+    }
+}
+
+impl Operation for Multiply {
+    #[inline(always)]
+    fn apply<W: Word>(a: W, b: W, overflow: Overflow) -> Option<W> {
+        Op::Multiply.apply(a, b, overflow)
+    }
+}
+
+impl Operation for LessThan {
+    #[inline(always)]
+    fn apply<W: Word>(a: W, b: W, _: Overflow) -> Option<W> {
+        Some(if a < b { W::ONE } else { W::ZERO })
+    }
+}
+
+impl Operation for Equal {
+    #[inline(always)]
+    fn apply<W: Word>(a: W, b: W, _: Overflow) -> Option<W> {
+        Some(if a == b { W::ONE } else { W::ZERO })
+    }
+}
+
+trait Condition: Send + Sync + 'static {
+    fn holds<W: Word>(value: W) -> bool;
+}
+
+struct IfTrue;
+struct IfFalse;
+
+impl Condition for IfTrue {
+    #[inline(always)]
+    fn holds<W: Word>(value: W) -> bool {
+        value != W::ZERO
+    }
+}
+
+impl Condition for IfFalse {
+    #[inline(always)]
+    fn holds<W: Word>(value: W) -> bool {
+        value == W::ZERO
+    }
+}
+
+#[inline(always)]
+fn store<M: Memory>(
+    computer: &mut Computer<M>,
+    address: usize,
+    index: usize,
+    target: usize,
+    value: M::Word,
+) -> Result<Flow<M::Word>, IntcodeError> {
+    attempt!(
+        computer,
+        address,
+        index,
+        computer.write::<true>(target, value)
+    );
+
+    if computer.modified_code.is_some() {
+        Ok(Flow::Modified)
+    } else {
+        Ok(Flow::Next)
+    }
+}
+
+fn binary<M, O, A, B, C>(address: usize, a: A, b: B, c: C) -> Step<M>
+where
+    M: Memory,
+    O: Operation,
+    A: Source<M::Word>,
+    B: Source<M::Word>,
+    C: Target,
+{
+    Box::new(move |computer| {
+        let a = attempt!(computer, address, 0, a.get(computer));
+        let b = attempt!(computer, address, 1, b.get(computer));
+        let c = attempt!(computer, address, 2, c.address(computer));
+
+        match O::apply(a, b, computer.overflow) {
+            Some(value) => store(computer, address, 2, c, value),
+            None => {
+                computer.ip = address;
+                Err(computer.instruction_fault(IntcodeErrorReason::Overflow))
+            }
+        }
+    })
+}
+
+fn binary_step<M: Memory, O: Operation>(
+    address: usize,
+    operands: [Operand<M::Word>; 3],
+) -> Step<M> {
+    with_source!(operands[0], a => with_source!(operands[1], b => with_target!(operands[2], c => {
+        binary::<M, O, _, _, _>(address, a, b, c)
+    })))
+}
+
+fn jump<M, C, A, B>(address: usize, a: A, b: B) -> Step<M>
+where
+    M: Memory,
+    C: Condition,
+    A: Source<M::Word>,
+    B: Source<M::Word>,
+{
+    Box::new(move |computer| {
+        let test = attempt!(computer, address, 0, a.get(computer));
+        let target = attempt!(computer, address, 1, b.get(computer));
+
+        if !C::holds(test) {
+            Ok(Flow::Next)
+        } else {
+            let target = attempt!(computer, address, 1, to_address(target, false));
+
+            Ok(Flow::Jump(target as usize))
+        }
+    })
+}
+
+fn jump_step<M: Memory, C: Condition>(address: usize, operands: [Operand<M::Word>; 3]) -> Step<M> {
+    with_source!(operands[0], a => with_source!(operands[1], b => jump::<M, C, _, _>(address, a, b)))
+}
+
+fn input<M: Memory, A: Target>(address: usize, a: A) -> Step<M> {
+    Box::new(move |computer| {
+        let a = attempt!(computer, address, 0, a.address(computer));
+
+        match computer.read_input() {
+            Some(value) => store(computer, address, 0, a, value),
+            None => Ok(Flow::NeedsInput),
+        }
+    })
+}
+
+fn output<M: Memory, A: Source<M::Word>>(address: usize, a: A) -> Step<M> {
+    Box::new(move |computer| {
+        let value = attempt!(computer, address, 0, a.get(computer));
+        computer.outputs.push(value);
+
+        Ok(Flow::Output(value))
+    })
+}
+
+fn adjust_relative_base<M: Memory, A: Source<M::Word>>(address: usize, a: A) -> Step<M> {
+    Box::new(move |computer| {
+        let value = attempt!(computer, address, 0, a.get(computer));
+        let value = attempt!(computer, address, 0, to_address(value, true));
+        computer.relative_base_offset = attempt!(
+            computer,
+            address,
+            0,
+            adjusted_relative_base(computer.relative_base_offset, value)
+        );
+
+        Ok(Flow::Next)
+    })
+}
+
+fn compile_instruction<M: Memory>(
+    address: usize,
+    instruction: &Instruction<M::Word>,
+    operands: [Operand<M::Word>; 3],
+) -> Step<M> {
+    match instruction {
+        Instruction::Op(Op::Add, _, _, _) => binary_step::<M, Add>(address, operands),
+        Instruction::Op(Op::Multiply, _, _, _) => binary_step::<M, Multiply>(address, operands),
+        Instruction::Compare(ComparisonOp::LessThan, _, _, _) => {
+            binary_step::<M, LessThan>(address, operands)
+        }
+        Instruction::Compare(ComparisonOp::Equal, _, _, _) => {
+            binary_step::<M, Equal>(address, operands)
+        }
+        Instruction::ConditionalJump(JumpCondition::IfTrue, _, _) => {
+            jump_step::<M, IfTrue>(address, operands)
+        }
+        Instruction::ConditionalJump(JumpCondition::IfFalse, _, _) => {
+            jump_step::<M, IfFalse>(address, operands)
+        }
+        Instruction::Input(_) => with_target!(operands[0], a => input(address, a)),
+        Instruction::Output(_) => with_source!(operands[0], a => output(address, a)),
+        Instruction::RelativeBaseAdjust(_) => {
+            with_source!(operands[0], a => adjust_relative_base(address, a))
+        }
+        Instruction::Halt => Box::new(|_| Ok(Flow::Halt)),
+        // Extensions aren't decoded while compiling, they're left to the interpreter
+        Instruction::Extended(_) => unreachable!("Extended instructions aren't compiled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::{self, Command};
+
+    use super::{basic_blocks, emit_rust};
+    use crate::intcode::program::Program;
+    use crate::intcode_computer::{
+        Backend, Computer, ExecutionState, IntcodeErrorReason, Memory, Overflow,
+    };
+
+    /// Runs the machine on the inputs given as arguments, queueing each one only once the machine
+    /// waits for it, and prints how it exited and its outputs.
+    const EMITTED_MAIN: &str = "
+fn main() {
+    let mut inputs = std::env::args().skip(1).map(|arg| arg.parse::<isize>().unwrap());
+    let mut machine = Machine::new();
+    let exit = loop {
+        match machine.run() {
+            Exit::NeedsInput => match inputs.next() {
+                Some(input) => machine.inputs.push_back(input),
+                None => break Exit::NeedsInput,
+            },
+            exit => break exit,
+        }
+    };
+    println!(\"{:?} {:?}\", exit, machine.outputs);
+}
+";
+
+    fn compiled(program: &[isize]) -> Computer {
+        let mut computer = Computer::new(program.to_vec());
+        computer.set_backend(Backend::Compiled);
+
+        computer
+    }
+
+    #[test]
+    fn test_basic_blocks() {
+        // Counts down from the input, outputting every value
+        let program = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+        let blocks: Vec<_> = basic_blocks(&program)
+            .iter()
+            .map(|block| (block.start, block.instructions.len(), block.end()))
+            .collect();
+
+        assert_eq!(blocks, vec![(0, 1, 2), (2, 3, 11), (11, 1, 12)]);
+    }
+
+    #[test]
+    fn test_compiled_matches_interpreter() {
+        // Counts down from the input, outputting every value
+        let program = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+        let mut computer = compiled(&program);
+
+        assert_eq!(computer.try_run(true), Ok(ExecutionState::NeedsInput));
+        computer.push_input(3);
+        assert_eq!(computer.try_run(true), Ok(ExecutionState::Output(3)));
+        assert_eq!(computer.ip(), 4);
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[3, 2, 1]);
+        assert_eq!(computer.ip(), 11);
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn test_self_modifying_code_falls_back() {
+        // Outputs 1, rewrites the operand of its own output instruction to 7 and loops once
+        let program = [
+            104, 1, 1101, 0, 7, 1, 1005, 17, 16, 1101, 0, 1, 17, 1105, 1, 0, 99, 0,
+        ];
+        let mut computer = compiled(&program);
+
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[1, 7]);
+
+        // Replacing the opcode of the output with a halt
+        let mut computer = compiled(&[1101, 0, 99, 4, 104, 1, 99]);
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[]);
+        assert_eq!(computer.ip(), 4);
+
+        let mut computer = compiled(&program);
+        assert_eq!(computer.try_run(true), Ok(ExecutionState::Output(1)));
+        computer.memory_mut().write(0, 99).unwrap();
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.ip(), 0);
+    }
+
+    #[test]
+    fn test_interpreted_writes_to_compiled_code() {
+        // Outputs 1, rewrites the operand of its own output instruction to 7 and loops once
+        let program = [
+            104, 1, 1101, 0, 7, 1, 1005, 17, 16, 1101, 0, 1, 17, 1105, 1, 0, 99, 0,
+        ];
+        let mut computer = compiled(&program);
+        assert_eq!(computer.try_run(true), Ok(ExecutionState::Output(1)));
+
+        // The interpreter rewrites the output after it was compiled
+        computer.set_backend(Backend::Interpreter);
+        assert_eq!(computer.step(), Ok(None));
+        assert_eq!(computer.memory().read(1), 7);
+
+        computer.set_backend(Backend::Compiled);
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[1, 7]);
+    }
+
+    #[test]
+    fn test_compiled_faults() {
+        let mut computer = compiled(&[109, -5, 1101, 1, 1, 20, 2201, 1, 0, 0, 99]);
+
+        let error = computer.try_run(false).unwrap_err();
+        assert_eq!(error.ip, 6);
+        assert_eq!(error.opcode, 2201);
+        assert_eq!(error.parameter, Some(0));
+        assert_eq!(computer.memory().read(20), 2);
+
+        let mut computer = compiled(&[1101, 2, 3, 5, 42]);
+        assert_eq!(computer.try_run(false).unwrap_err().ip, 4);
+
+        let mut computer = compiled(&[1102, isize::MAX, 2, 7, 104, 0, 99, 0]);
+        computer.set_overflow(Overflow::Trap);
+        let error = computer.try_run(false).unwrap_err();
+        assert_eq!((error.ip, error.reason), (0, IntcodeErrorReason::Overflow));
+    }
+
+    #[test]
+    fn test_emit_rust() {
+        let source = emit_rust(&[3, 9, 1001, 9, -1, 9, 1005, 9, 2, 99]);
+
+        assert!(
+            source.contains("const PROGRAM: [isize; 10] = [3, 9, 1001, 9, -1, 9, 1005, 9, 2, 99];")
+        );
+        assert!(source.contains("matches!(address, 0..=9)"));
+        assert!(source.contains(
+            "            2 => {\n\
+             \x20               // 0002: add [9], #-1, [9]\n\
+             \x20               let value = isize::wrapping_add(self.read(9), -1);\n\
+             \x20               let address = 9;\n\
+             \x20               if self.write(address, value) {\n\
+             \x20                   self.ip = 6;\n\
+             \x20                   return Ok(Exit::Fallback);\n\
+             \x20               }\n\
+             \x20               // 0006: jnz [9], #2\n\
+             \x20               let test = self.read(9);\n\
+             \x20               let target = 2;\n\
+             \x20               if test != 0 {\n\
+             \x20                   self.ip = Self::target(target, 6)?;\n\
+             \x20                   continue;\n\
+             \x20               }\n\
+             \x20               self.ip = 9;\n\
+             \x20           }\n"
+        ));
+    }
+
+    fn emit_dir() -> PathBuf {
+        env::temp_dir().join(format!("intcode-emit-{}", process::id()))
+    }
+
+    /// Build the module emitted for `program` into an executable, with overflow checks enabled.
+    fn build_emitted(name: &str, program: &[isize]) -> PathBuf {
+        let dir = emit_dir();
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join(format!("{}.rs", name));
+        let binary = dir.join(name);
+        fs::write(&source, emit_rust(program) + EMITTED_MAIN).unwrap();
+
+        let status = Command::new("rustc")
+            .args(["--edition", "2018", "-C", "overflow-checks=on", "-o"])
+            .arg(&binary)
+            .arg(&source)
+            .status()
+            .expect("rustc should be installed");
+        assert!(
+            status.success(),
+            "The module emitted for {} doesn't build",
+            name
+        );
+
+        binary
+    }
+
+    fn run_emitted(binary: &Path, inputs: &[isize]) -> String {
+        let output = Command::new(binary)
+            .args(inputs.iter().map(ToString::to_string))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    fn interpret(program: &[isize], inputs: &[isize]) -> String {
+        let mut computer = Computer::new(program.to_vec());
+        computer.push_inputs(inputs.to_vec());
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+
+        format!("Halted {:?}", computer.all_outputs())
+    }
+
+    #[test]
+    fn test_emitted_rust_matches_interpreter() {
+        let day09 = Program::from_file("day09.txt").unwrap().words();
+        let max = isize::MAX;
+        let programs = vec![
+            // Counts down from the input, outputting every value
+            (
+                "countdown",
+                vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0],
+                vec![vec![3]],
+            ),
+            // Doubles the largest word and adds one to it, both of which wrap around
+            (
+                "wrapping",
+                vec![1102, max, 2, 13, 1001, 13, 1, 14, 4, 13, 4, 14, 99, 0, 0],
+                vec![vec![]],
+            ),
+            // Outputs before waiting for input, so it resumes in the middle of its only block
+            (
+                "resume",
+                vec![104, 1, 3, 9, 4, 9, 99, 0, 0, 0],
+                vec![vec![5]],
+            ),
+            // Reaches most of its code through jump tables and return addresses
+            ("day09", day09, vec![vec![1], vec![2]]),
+        ];
+
+        for (name, program, runs) in &programs {
+            let binary = build_emitted(name, program);
+
+            for inputs in runs {
+                assert_eq!(
+                    run_emitted(&binary, inputs),
+                    interpret(program, inputs),
+                    "{} with inputs {:?}",
+                    name,
+                    inputs
+                );
+            }
+        }
+
+        let _ = fs::remove_dir_all(emit_dir());
+    }
+}
@@ -0,0 +1,379 @@
+//! An interactive debugger built on [`Computer::step`].
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::intcode::disasm::Line;
+use crate::intcode_computer::{Computer, ExecutionState, Instruction, Memory};
+
+const HELP: &str = "\
+step [n]            Execute n instructions, defaults to 1 (s)
+continue            Run until a breakpoint, watchpoint, halt or missing input (c)
+break <addr>        Set a breakpoint (b)
+delete <addr>       Remove a breakpoint (d)
+watch <addr>        Stop after writes to an address (w)
+unwatch <addr>      Remove a watchpoint
+input <v> [v ...]   Queue input values (i)
+regs                Show ip and relative base (r)
+x <addr> [count]    Show memory, count defaults to 1
+list [n]            Decode n instructions from ip, defaults to 5 (l)
+outputs             Take the outputs produced so far (o)
+help                Show this help (h)
+quit                Exit (q)";
+
+/// What the caller should do after a command has been executed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+}
+
+/// Why stepping stopped before running to completion.
+enum Stop {
+    Breakpoint(usize),
+    Watchpoint(usize, isize, isize),
+    State(ExecutionState),
+    Fault,
+}
+
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+}
+
+fn parse_address(argument: Option<&str>) -> Result<usize, String> {
+    let argument = argument.ok_or_else(|| "Expected an address".to_string())?;
+
+    argument
+        .parse()
+        .map_err(|_| format!("Invalid address `{}`", argument))
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn computer(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    /// The instruction at the instruction pointer, decoded like the disassembler does.
+    pub fn current(&mut self) -> String {
+        let ip = self.computer.ip();
+
+        match self.computer.current_instruction() {
+            Ok(instruction) => Line::Instruction {
+                address: ip,
+                instruction,
+            }
+            .to_string(),
+            Err(error) => format!("{:04}: <{}>", ip, error),
+        }
+    }
+
+    /// Execute a single command, writing anything it prints to `out`.
+    pub fn execute(&mut self, command: &str, out: &mut String) -> Action {
+        let mut words = command.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Action::Continue,
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let result = match name {
+            "s" | "step" => arguments
+                .first()
+                .map_or(Ok(1), |count| {
+                    count
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid count `{}`", count))
+                })
+                .map(|count| self.run(Some(count), out)),
+            "c" | "continue" => {
+                self.run(None, out);
+                Ok(())
+            }
+            "b" | "break" => parse_address(arguments.first().cloned()).map(|address| {
+                self.breakpoints.insert(address);
+            }),
+            "d" | "delete" => parse_address(arguments.first().cloned()).map(|address| {
+                self.breakpoints.remove(&address);
+            }),
+            "w" | "watch" => parse_address(arguments.first().cloned()).map(|address| {
+                self.watchpoints.insert(address);
+            }),
+            "unwatch" => parse_address(arguments.first().cloned()).map(|address| {
+                self.watchpoints.remove(&address);
+            }),
+            "i" | "input" => arguments
+                .iter()
+                .map(|value| {
+                    value
+                        .parse::<isize>()
+                        .map_err(|_| format!("Invalid input `{}`", value))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(|values| self.computer.push_inputs(values)),
+            "r" | "regs" => {
+                let _ = writeln!(
+                    out,
+                    "ip: {}\nrelative_base_offset: {}\nhalted: {}",
+                    self.computer.ip(),
+                    self.computer.relative_base_offset(),
+                    self.computer.is_halted()
+                );
+                Ok(())
+            }
+            "x" => parse_address(arguments.first().cloned()).and_then(|address| {
+                let count = arguments.get(1).map_or(Ok(1), |count| {
+                    count
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid count `{}`", count))
+                })?;
+
+                for offset in 0..count {
+                    let address = address
+                        .checked_add(offset)
+                        .ok_or_else(|| "Address out of range".to_string())?;
+                    let _ = writeln!(
+                        out,
+                        "{:04}: {}",
+                        address,
+                        self.computer.memory().read(address)
+                    );
+                }
+
+                Ok(())
+            }),
+            "l" | "list" => arguments
+                .first()
+                .map_or(Ok(5), |count| {
+                    count
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid count `{}`", count))
+                })
+                .and_then(|count| self.list(count, out)),
+            "o" | "outputs" => {
+                let outputs: Vec<_> = self
+                    .computer
+                    .drain_outputs()
+                    .map(|value| value.to_string())
+                    .collect();
+                let _ = writeln!(out, "{}", outputs.join(","));
+                Ok(())
+            }
+            "h" | "help" => {
+                let _ = writeln!(out, "{}", HELP);
+                Ok(())
+            }
+            "q" | "quit" => return Action::Quit,
+            _ => Err(format!("Unknown command `{}`, try `help`", name)),
+        };
+
+        if let Err(error) = result {
+            let _ = writeln!(out, "{}", error);
+        }
+
+        Action::Continue
+    }
+
+    fn list(&mut self, count: usize, out: &mut String) -> Result<(), String> {
+        let mut address = self.computer.ip();
+
+        for _ in 0..count {
+            let end = address
+                .checked_add(4)
+                .ok_or_else(|| "Address out of range".to_string())?;
+            let memory = self.computer.memory();
+            let words: Vec<_> = (address..end).map(|word| memory.read(word)).collect();
+
+            match Instruction::parse(address, &words) {
+                Ok(instruction) => {
+                    let _ = writeln!(
+                        out,
+                        "{}",
+                        Line::Instruction {
+                            address,
+                            instruction
+                        }
+                    );
+                    address += instruction.length();
+                }
+                Err(_) => {
+                    let _ = writeln!(
+                        out,
+                        "{}",
+                        Line::Data {
+                            address,
+                            value: words[0]
+                        }
+                    );
+                    address += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Step `limit` instructions, or until something interesting happens when `limit` is `None`.
+    fn run(&mut self, limit: Option<usize>, out: &mut String) {
+        let mut executed = 0;
+
+        let stop = loop {
+            if limit.is_some_and(|limit| executed >= limit) {
+                break None;
+            }
+
+            let ip = self.computer.ip();
+            if limit.is_none() && executed > 0 && self.breakpoints.contains(&ip) {
+                break Some(Stop::Breakpoint(ip));
+            }
+
+            let relative_base_offset = self.computer.relative_base_offset() as usize;
+            let watched = match self.computer.current_instruction() {
+                Ok(instruction) => instruction
+                    .write_parameter()
+                    .and_then(|parameter| parameter.address(relative_base_offset).ok())
+                    .filter(|address| self.watchpoints.contains(address)),
+                Err(_) => None,
+            };
+            let old_value = watched.map(|address| self.computer.memory().read(address));
+
+            match self.computer.step() {
+                Err(error) => {
+                    let _ = writeln!(out, "fault: {}", error);
+                    break Some(Stop::Fault);
+                }
+                Ok(Some(ExecutionState::NeedsInput)) => {
+                    break Some(Stop::State(ExecutionState::NeedsInput))
+                }
+                Ok(state) => {
+                    executed += 1;
+
+                    if let Some(ExecutionState::Output(value)) = state {
+                        let _ = writeln!(out, "output: {}", value);
+                    }
+
+                    if let (Some(address), Some(old_value)) = (watched, old_value) {
+                        let new_value = self.computer.memory().read(address);
+                        break Some(Stop::Watchpoint(address, old_value, new_value));
+                    }
+
+                    if state == Some(ExecutionState::Halted) {
+                        break Some(Stop::State(ExecutionState::Halted));
+                    }
+                }
+            }
+        };
+
+        match stop {
+            Some(Stop::Breakpoint(address)) => {
+                let _ = writeln!(out, "breakpoint at {}", address);
+            }
+            Some(Stop::Watchpoint(address, old_value, new_value)) => {
+                let _ = writeln!(
+                    out,
+                    "watchpoint at {}: {} -> {}",
+                    address, old_value, new_value
+                );
+            }
+            Some(Stop::State(ExecutionState::NeedsInput)) => {
+                let _ = writeln!(out, "waiting for input");
+            }
+            Some(Stop::State(ExecutionState::Halted)) => {
+                let _ = writeln!(out, "halted");
+            }
+            Some(Stop::State(_)) | Some(Stop::Fault) | None => (),
+        }
+
+        let current = self.current();
+        let _ = writeln!(out, "{}", current);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Debugger};
+    use crate::intcode_computer::Computer;
+
+    fn execute(debugger: &mut Debugger, command: &str) -> String {
+        let mut out = String::new();
+        assert_eq!(debugger.execute(command, &mut out), Action::Continue);
+
+        out
+    }
+
+    fn debugger() -> Debugger {
+        // Reads two values and outputs their sum
+        Debugger::new(Computer::new(vec![
+            3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0,
+        ]))
+    }
+
+    #[test]
+    fn test_step_and_inspect() {
+        let mut debugger = debugger();
+
+        assert_eq!(debugger.current(), "0000: in [11]");
+        assert_eq!(
+            execute(&mut debugger, "step"),
+            "waiting for input\n0000: in [11]\n"
+        );
+        assert_eq!(execute(&mut debugger, "input 4 5"), "");
+        assert_eq!(
+            execute(&mut debugger, "s 2"),
+            "0004: add [11], [12], [13]\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "x 11 3"),
+            "0011: 4\n0012: 5\n0013: 0\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "x 18446744073709551615 2"),
+            "18446744073709551615: 0\nAddress out of range\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "regs"),
+            "ip: 4\nrelative_base_offset: 0\nhalted: false\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "c"),
+            "output: 9\nhalted\n0010: hlt\n"
+        );
+        assert_eq!(execute(&mut debugger, "outputs"), "9\n");
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut debugger = debugger();
+        execute(&mut debugger, "input 1 2");
+
+        assert_eq!(execute(&mut debugger, "break 8"), "");
+        assert_eq!(execute(&mut debugger, "watch 12"), "");
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "watchpoint at 12: 0 -> 2\n0004: add [11], [12], [13]\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "breakpoint at 8\n0008: out [13]\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "list 2"),
+            "0008: out [13]\n0010: hlt\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "nope"),
+            "Unknown command `nope`, try `help`\n"
+        );
+
+        let mut out = String::new();
+        assert_eq!(debugger.execute("quit", &mut out), Action::Quit);
+    }
+}
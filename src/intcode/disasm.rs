@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::intcode_computer::{Instruction, JumpCondition, Op, Parameter};
+
+/// A single line of a disassembly listing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        instruction: Instruction,
+    },
+    Data {
+        address: usize,
+        value: isize,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match *self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => address,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Line::Instruction {
+                address,
+                instruction,
+            } => write!(f, "{:04}: {}", address, instruction),
+            Line::Data { address, value } => write!(f, "{:04}: .data {}", address, value),
+        }
+    }
+}
+
+/// A disassembled program, one [`Line`] per instruction or data word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub lines: Vec<Line>,
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode the instruction at `address`, treating memory past the end of the program as zeros.
+///
+/// Returns `None` if the words don't decode or the instruction runs past the end of the program.
+pub(crate) fn decode(program: &[isize], address: usize) -> Option<Instruction> {
+    let mut window = [0; 4];
+    for (offset, word) in program.iter().skip(address).take(4).enumerate() {
+        window[offset] = *word;
+    }
+
+    Instruction::parse(address, &window)
+        .ok()
+        .filter(|instruction| address + instruction.length() <= program.len())
+}
+
+/// Addresses control can statically be shown to flow to after executing `instruction`.
+///
+/// Jumps whose condition is an immediate are resolved, and jumps to non immediate targets are
+/// dynamic so only their fall through is known. Immediates stored in `written` words are
+/// overwritten by the program itself and therefore aren't treated as constants.
+pub(crate) fn static_successors(
+    address: usize,
+    instruction: &Instruction,
+    written: &HashSet<usize>,
+) -> Vec<usize> {
+    let next = address + instruction.length();
+
+    match *instruction {
+        Instruction::Halt => vec![],
+        Instruction::ConditionalJump(condition, test, target) => {
+            let taken = match test {
+                Parameter::Immediate(value) if !written.contains(&(address + 1)) => {
+                    Some(match condition {
+                        JumpCondition::IfTrue => value != 0,
+                        JumpCondition::IfFalse => value == 0,
+                    })
+                }
+                _ => None,
+            };
+            let target = match target {
+                Parameter::Immediate(target)
+                    if target >= 0 && !written.contains(&(address + 2)) =>
+                {
+                    Some(target as usize)
+                }
+                _ => None,
+            };
+
+            match taken {
+                Some(true) => target.into_iter().collect(),
+                Some(false) => vec![next],
+                None => std::iter::once(next).chain(target).collect(),
+            }
+        }
+        _ => vec![next],
+    }
+}
+
+/// The address `instruction` writes to, if it is known without running the program.
+pub(crate) fn static_write(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::Op(_, _, _, Parameter::Position(address))
+        | Instruction::Compare(_, _, _, Parameter::Position(address))
+        | Instruction::Input(Parameter::Position(address)) => Some(address),
+        _ => None,
+    }
+}
+
+/// An immediate value stored to memory that looks like the address of code.
+///
+/// Compiled Intcode calls functions by storing the return address relative to the relative base
+/// and then jumping, and dispatches through tables of addresses stored at fixed positions. Both
+/// hide the code they lead to from [`static_successors`].
+pub(crate) fn code_pointer(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::Op(
+            op,
+            Parameter::Immediate(a),
+            Parameter::Immediate(b),
+            Parameter::Relative(_) | Parameter::Position(_),
+        ) => {
+            let value = match op {
+                Op::Add => a.checked_add(b)?,
+                Op::Multiply => a.checked_mul(b)?,
+            };
+
+            if value >= 0 {
+                Some(value as usize)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn explore(program: &[isize], written: &HashSet<usize>) -> BTreeMap<usize, Instruction> {
+    let mut instructions = BTreeMap::new();
+    let mut is_code = vec![false; program.len()];
+    let mut pending = vec![0];
+    // Only guesses, explored once everything that control certainly reaches is known, so they
+    // can't claim the words of real instructions
+    let mut pointers = vec![];
+
+    while let Some(address) = pending.pop().or_else(|| pointers.pop()) {
+        if address >= program.len() || instructions.contains_key(&address) {
+            continue;
+        }
+
+        let instruction = match decode(program, address) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        let words = address..address + instruction.length();
+
+        // Overlapping instructions only happen with self modifying code, keep the first one
+        if is_code[words.clone()].iter().any(|&code| code) {
+            continue;
+        }
+
+        for word in words {
+            is_code[word] = true;
+        }
+        instructions.insert(address, instruction);
+
+        pending.extend(static_successors(address, &instruction, written));
+        pointers.extend(code_pointer(&instruction));
+    }
+
+    instructions
+}
+
+/// Find every instruction reachable from address 0, keyed by address.
+///
+/// Exploration is repeated until the set of statically written addresses stops growing, since
+/// each write can turn a seemingly constant jump into a dynamic one.
+pub(crate) fn reachable_instructions(program: &[isize]) -> BTreeMap<usize, Instruction> {
+    let mut written = HashSet::new();
+
+    loop {
+        let instructions = explore(program, &written);
+        let previously_written = written.len();
+        written.extend(instructions.values().filter_map(static_write));
+
+        if written.len() == previously_written {
+            return instructions;
+        }
+    }
+}
+
+/// Disassemble a program.
+///
+/// Instructions are discovered by following control flow from address 0, every word that isn't
+/// part of a reachable instruction is listed as `.data`.
+pub fn disassemble(program: &[isize]) -> Listing {
+    let instructions = reachable_instructions(program);
+    let mut lines = vec![];
+    let mut address = 0;
+
+    while address < program.len() {
+        match instructions.get(&address) {
+            Some(&instruction) => {
+                lines.push(Line::Instruction {
+                    address,
+                    instruction,
+                });
+                address += instruction.length();
+            }
+            None => {
+                lines.push(Line::Data {
+                    address,
+                    value: program[address],
+                });
+                address += 1;
+            }
+        }
+    }
+
+    Listing { lines }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+
+    #[test]
+    fn test_disassemble() {
+        let program = [1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            "0000: add [9], [10], [3]\n\
+             0004: mul [3], [11], [0]\n\
+             0008: hlt\n\
+             0009: .data 30\n\
+             0010: .data 40\n\
+             0011: .data 50\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_modes_and_jumps() {
+        let program = [
+            109, -3, 21101, 9, 0, 2, 1105, 1, 12, 204, 7, 99, 1006, 20, 9, 99,
+        ];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            "0000: arb #-3\n\
+             0002: add #9, #0, [rb+2]\n\
+             0006: jnz #1, #12\n\
+             0009: out [rb+7]\n\
+             0011: hlt\n\
+             0012: jz [20], #9\n\
+             0015: hlt\n"
+        );
+    }
+
+    #[test]
+    fn test_self_modified_jump_conditions_are_dynamic() {
+        // The condition of the jump at 6 is overwritten by the comparison at 2
+        let program = [3, 20, 1007, 20, 2, 7, 1105, -1, 12, 104, 1, 99, 104, 2, 99];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            "0000: in [20]\n\
+             0002: lt [20], #2, [7]\n\
+             0006: jnz #-1, #12\n\
+             0009: out #1\n\
+             0011: hlt\n\
+             0012: out #2\n\
+             0014: hlt\n"
+        );
+    }
+
+    #[test]
+    fn test_unreachable_words_are_data() {
+        let program = [1106, 0, 4, 1, 3, 20, 99];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            "0000: jz #0, #4\n\
+             0003: .data 1\n\
+             0004: in [20]\n\
+             0006: hlt\n"
+        );
+    }
+
+    #[test]
+    fn test_jump_tables() {
+        // Stores the address of the output in a table and jumps through it
+        let program = [1101, 8, 0, 20, 6, 21, 20, 99, 104, 5, 99];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            "0000: add #8, #0, [20]\n\
+             0004: jz [21], [20]\n\
+             0007: hlt\n\
+             0008: out #5\n\
+             0010: hlt\n"
+        );
+    }
+
+    #[test]
+    fn test_overflowing_code_pointers() {
+        let program = [21101, isize::MAX, 1, 0, 21102, isize::MAX, 2, 0, 99];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+            format!(
+                "0000: add #{0}, #1, [rb+0]\n\
+                 0004: mul #{0}, #2, [rb+0]\n\
+                 0008: hlt\n",
+                isize::MAX
+            )
+        );
+    }
+}
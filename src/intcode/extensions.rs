@@ -0,0 +1,78 @@
+//! Built-in extension opcodes for variant Intcode machines, enabled by the `extensions` feature.
+//!
+//! | Opcode | Instruction | Effect                                                       |
+//! |--------|-------------|--------------------------------------------------------------|
+//! | 10     | `yld`       | Return [`ExecutionState::Yielded`] from the current run      |
+//! | 11     | `slp a`     | Sleep for `a` milliseconds                                   |
+//! | 12     | `dbg a`     | Print `a`, with the address of the instruction, to stderr    |
+//!
+//! [`ExecutionState::Yielded`]: crate::intcode_computer::ExecutionState::Yielded
+use std::thread;
+use std::time::Duration;
+
+use crate::intcode_computer::{Access, Effect, Extensions, Memory, Word};
+
+pub const YIELD: usize = 10;
+pub const SLEEP: usize = 11;
+pub const DEBUG_PRINT: usize = 12;
+
+/// Extensions with every built-in opcode registered, see
+/// [`Computer::set_extensions`](crate::intcode_computer::Computer::set_extensions).
+pub fn builtins<M: Memory>() -> Extensions<M> {
+    let mut extensions = Extensions::new();
+    register_builtins(&mut extensions);
+
+    extensions
+}
+
+/// Register every built-in opcode with `extensions`.
+pub fn register_builtins<M: Memory>(extensions: &mut Extensions<M>) {
+    extensions.register(YIELD, "yld", &[], |_| Ok(Effect::Yield));
+
+    extensions.register(SLEEP, "slp", &[Access::Read], |operands| {
+        let milliseconds = operands.read(0)?.to_isize().unwrap_or(isize::MAX).max(0);
+        thread::sleep(Duration::from_millis(milliseconds as u64));
+
+        Ok(Effect::Continue)
+    });
+
+    extensions.register(DEBUG_PRINT, "dbg", &[Access::Read], |operands| {
+        let value = operands.read(0)?;
+        eprintln!("{:04}: {}", operands.computer().ip(), value);
+
+        Ok(Effect::Continue)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::builtins;
+    use crate::intcode_computer::{Backend, Computer, ExecutionState};
+
+    #[test]
+    fn test_builtins() {
+        // Yields, outputs 1, sleeps, prints 2 for debugging and yields again before halting
+        let program = vec![10, 104, 1, 1011, 0, 1012, 2, 10, 99];
+
+        for &backend in &[Backend::Interpreter, Backend::Compiled] {
+            let mut computer = Computer::new(program.clone());
+            computer.set_backend(backend);
+            computer.set_extensions(Some(Arc::new(builtins())));
+
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Yielded));
+            assert_eq!(computer.ip(), 1);
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Yielded));
+            assert_eq!(computer.all_outputs(), &[1]);
+            assert_eq!(
+                computer.current_instruction().map(|i| i.to_string()),
+                Ok("hlt".to_string())
+            );
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        }
+
+        let mut computer = Computer::new(program);
+        assert!(computer.try_run(false).is_err());
+    }
+}
@@ -0,0 +1,606 @@
+//! Differential testing of the ways a [`Computer`] can execute a program.
+//!
+//! [`generate`] builds random, well-formed programs: every standard opcode in every valid mode,
+//! jumps to the start of other instructions, loops that count down to zero and the occasional
+//! write into the program's own code. [`check`] runs a [`Case`] on a reference [`Variant`] and
+//! on alternatives to it, and compares everything observable about the runs. Programs that never
+//! halt are stopped by a step budget, so the state at that point is compared instead.
+//!
+//! A [`Case`] that behaves differently is shrunk with [`minimize`] and can be written to a
+//! regression fixture with [`Display`](fmt::Display), every fixture in `fuzz/` is checked by the
+//! tests. `cargo run --bin intcode-fuzz` does all of that until it finds a difference.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::intcode::snapshot::Snapshot;
+use crate::intcode_computer::{
+    Backend, Computer, ExecutionState, IntcodeError, Memory, PagedMemory,
+};
+
+/// Number of instructions a case may execute before its state is compared.
+pub const STEP_BUDGET: u64 = 10_000;
+
+const HEADER: &str = "intcode-case 1";
+
+/// Number of words after the code that position and relative mode parameters mostly refer to.
+const DATA_WORDS: usize = 16;
+/// The relative base starts this far into the data, so small negative offsets stay in it.
+const RELATIVE_BASE: usize = 4;
+
+/// A xorshift generator, which is all the randomness the fuzzer needs.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Self(seed ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        self.0
+    }
+
+    /// A number in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// A number in `low..=high`.
+    pub fn between(&mut self, low: isize, high: isize) -> isize {
+        low + self.below((high - low) as usize + 1) as isize
+    }
+
+    /// Whether an event with the given chance in percent happens.
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// A program and the inputs it's run with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub program: Vec<isize>,
+    pub inputs: Vec<isize>,
+}
+
+/// Everything observable about a run of a [`Case`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub result: Result<ExecutionState, IntcodeError>,
+    pub steps: u64,
+    /// Memory, `ip`, relative base, remaining inputs and outputs once the run stopped.
+    pub snapshot: Snapshot,
+}
+
+impl Outcome {
+    /// Run `computer` for at most [`STEP_BUDGET`] instructions, collecting all outputs.
+    pub fn of<M: Memory<Word = isize>>(computer: &mut Computer<M>) -> Self {
+        let result = computer.run_for(STEP_BUDGET, false);
+
+        Self {
+            result,
+            steps: computer.steps(),
+            snapshot: computer.snapshot(),
+        }
+    }
+}
+
+/// One way of running a case.
+#[derive(Debug, Copy, Clone)]
+pub struct Variant {
+    pub name: &'static str,
+    pub run: fn(&Case) -> Outcome,
+}
+
+fn run_with(case: &Case, backend: Backend, cache_instructions: bool) -> Outcome {
+    let mut computer = Computer::with_memory(PagedMemory::new(&case.program));
+    computer.set_backend(backend);
+    computer.set_instruction_cache(cache_instructions);
+    computer.push_inputs(case.inputs.iter().copied());
+
+    Outcome::of(&mut computer)
+}
+
+/// The interpreter decoding every instruction as it's reached, which every other variant has to
+/// agree with.
+pub const REFERENCE: Variant = Variant {
+    name: "uncached interpreter",
+    run: |case| run_with(case, Backend::Interpreter, false),
+};
+
+/// The optimized ways of running a program that the crate ships with.
+pub fn variants() -> Vec<Variant> {
+    vec![
+        Variant {
+            name: "cached interpreter",
+            run: |case| run_with(case, Backend::Interpreter, true),
+        },
+        Variant {
+            name: "compiled",
+            run: |case| run_with(case, Backend::Compiled, true),
+        },
+    ]
+}
+
+/// A variant that disagreed with the reference on a case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub variant: &'static str,
+    pub expected: Box<Outcome>,
+    pub actual: Box<Outcome>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (expected, actual) = (&self.expected, &self.actual);
+
+        writeln!(
+            f,
+            "The {} variant differs from the reference:",
+            self.variant
+        )?;
+        if expected.result != actual.result {
+            writeln!(
+                f,
+                "  result: expected {:?}, got {:?}",
+                expected.result, actual.result
+            )?;
+        }
+        if expected.steps != actual.steps {
+            writeln!(
+                f,
+                "  steps: expected {}, got {}",
+                expected.steps, actual.steps
+            )?;
+        }
+        if expected.snapshot != actual.snapshot {
+            writeln!(f, "  expected state:\n{}", expected.snapshot)?;
+            writeln!(f, "  actual state:\n{}", actual.snapshot)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Mismatch {}
+
+/// Run `case` on `reference` and every one of `variants`, returning the first disagreement.
+pub fn check(case: &Case, reference: &Variant, variants: &[Variant]) -> Result<(), Mismatch> {
+    let expected = (reference.run)(case);
+
+    for variant in variants {
+        let actual = (variant.run)(case);
+
+        if actual != expected {
+            return Err(Mismatch {
+                variant: variant.name,
+                expected: Box::new(expected),
+                actual: Box::new(actual),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// A parameter of a generated instruction, resolved to a word once the code's length is known.
+#[derive(Debug, Copy, Clone)]
+enum Operand {
+    Immediate(isize),
+    /// Position mode, a word of the data.
+    Data(usize),
+    /// Position mode, a word of the code.
+    Code(usize),
+    Relative(isize),
+    /// Immediate mode, the address of the instruction with that index.
+    Target(usize),
+}
+
+impl Operand {
+    fn mode(self) -> isize {
+        match self {
+            Operand::Data(_) | Operand::Code(_) => 0,
+            Operand::Immediate(_) | Operand::Target(_) => 1,
+            Operand::Relative(_) => 2,
+        }
+    }
+}
+
+fn read_operand(rng: &mut Rng) -> Operand {
+    match rng.below(10) {
+        0..=3 => Operand::Immediate(rng.between(-20, 20)),
+        4..=7 => Operand::Data(rng.below(DATA_WORDS)),
+        _ => Operand::Relative(rng.between(-3, 3)),
+    }
+}
+
+fn write_operand(rng: &mut Rng, code_words: usize) -> Operand {
+    match rng.below(20) {
+        0 => Operand::Code(rng.below(code_words)),
+        1..=13 => Operand::Data(rng.below(DATA_WORDS)),
+        _ => Operand::Relative(rng.between(-3, 3)),
+    }
+}
+
+/// Generate a random program of about `instructions` instructions, with inputs for it.
+pub fn generate(rng: &mut Rng, instructions: usize) -> Case {
+    // Every instruction takes at most four words, which bounds the targets of code writes
+    let code_words = instructions * 4;
+    let mut code: Vec<(isize, Vec<Operand>)> = vec![];
+
+    while code.len() < instructions {
+        let (opcode, operands) = match rng.below(20) {
+            0..=3 => (
+                1 + rng.below(2) as isize,
+                vec![
+                    read_operand(rng),
+                    read_operand(rng),
+                    write_operand(rng, code_words),
+                ],
+            ),
+            4..=6 => (
+                7 + rng.below(2) as isize,
+                vec![
+                    read_operand(rng),
+                    read_operand(rng),
+                    write_operand(rng, code_words),
+                ],
+            ),
+            7 => (3, vec![write_operand(rng, code_words)]),
+            8..=9 => (4, vec![read_operand(rng)]),
+            10..=12 => {
+                let target = if rng.chance(90) {
+                    Operand::Target(rng.below(instructions + 1))
+                } else {
+                    read_operand(rng)
+                };
+
+                (5 + rng.below(2) as isize, vec![read_operand(rng), target])
+            }
+            13 => (9, vec![Operand::Immediate(rng.between(-2, 2))]),
+            14 => (99, vec![]),
+            _ => {
+                // A loop counting a data word down to zero around a few other instructions
+                let counter = Operand::Data(rng.below(DATA_WORDS));
+                let start = code.len() + 1;
+
+                code.push((
+                    1,
+                    vec![
+                        Operand::Immediate(0),
+                        Operand::Immediate(rng.between(1, 5)),
+                        counter,
+                    ],
+                ));
+                for _ in 0..rng.between(1, 3) {
+                    code.push((
+                        1 + rng.below(2) as isize,
+                        vec![
+                            read_operand(rng),
+                            read_operand(rng),
+                            Operand::Data(rng.below(DATA_WORDS)),
+                        ],
+                    ));
+                }
+                code.push((1, vec![counter, Operand::Immediate(-1), counter]));
+
+                (5, vec![counter, Operand::Target(start)])
+            }
+        };
+
+        code.push((opcode, operands));
+    }
+
+    // The relative base is set by the first instruction, which shifts every other one by two
+    let mut addresses = vec![2];
+    for (_, operands) in &code {
+        addresses.push(addresses.last().unwrap() + 1 + operands.len());
+    }
+    let halt = *addresses.last().unwrap();
+    let data = halt + 1;
+
+    let mut program = vec![109, (data + RELATIVE_BASE) as isize];
+    for (opcode, operands) in &code {
+        let modes = operands
+            .iter()
+            .enumerate()
+            .map(|(index, operand)| operand.mode() * 10_isize.pow(index as u32 + 2))
+            .sum::<isize>();
+        program.push(opcode + modes);
+
+        for operand in operands {
+            program.push(match *operand {
+                Operand::Immediate(value) | Operand::Relative(value) => value,
+                Operand::Data(index) => (data + index) as isize,
+                Operand::Code(word) => (word % halt) as isize,
+                Operand::Target(index) => addresses[index.min(code.len())] as isize,
+            });
+        }
+    }
+    program.push(99);
+    program.extend((0..DATA_WORDS).map(|_| rng.between(-10, 10)));
+
+    let inputs = (0..rng.below(8)).map(|_| rng.between(-10, 10)).collect();
+
+    Case { program, inputs }
+}
+
+/// Shrink `case` while `fails` still holds for it.
+///
+/// Runs of words and inputs are removed, then single words are replaced with smaller ones, until
+/// neither makes any progress.
+pub fn minimize<F>(case: &Case, mut fails: F) -> Case
+where
+    F: FnMut(&Case) -> bool,
+{
+    let mut case = case.clone();
+
+    loop {
+        let mut progress = false;
+
+        for inputs in [false, true].iter().copied() {
+            let mut chunk = if inputs {
+                case.inputs.len()
+            } else {
+                case.program.len()
+            };
+
+            while chunk > 0 {
+                let mut start = 0;
+
+                loop {
+                    let words = if inputs { &case.inputs } else { &case.program };
+                    if start >= words.len() {
+                        break;
+                    }
+
+                    let mut candidate = case.clone();
+                    let words = if inputs {
+                        &mut candidate.inputs
+                    } else {
+                        &mut candidate.program
+                    };
+                    let end = (start + chunk).min(words.len());
+                    words.drain(start..end);
+
+                    if fails(&candidate) {
+                        case = candidate;
+                        progress = true;
+                    } else {
+                        start += chunk;
+                    }
+                }
+
+                chunk /= 2;
+            }
+        }
+
+        for index in 0..case.program.len() {
+            let word = case.program[index];
+
+            for &smaller in &[0, 1, word / 2] {
+                if smaller.abs() >= word.abs() {
+                    continue;
+                }
+
+                let mut candidate = case.clone();
+                candidate.program[index] = smaller;
+
+                if fails(&candidate) {
+                    case = candidate;
+                    progress = true;
+                    break;
+                }
+            }
+        }
+
+        if !progress {
+            return case;
+        }
+    }
+}
+
+/// A case that made a variant disagree with the reference, shrunk as far as possible.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    /// The seed and iteration the case was generated by.
+    pub seed: u64,
+    pub iteration: u64,
+    pub case: Case,
+    pub mismatch: Mismatch,
+}
+
+/// Check `iterations` programs generated from `seed` against the [`REFERENCE`], returning the
+/// first one that a variant disagrees on, after minimizing it.
+pub fn fuzz(seed: u64, iterations: u64, variants: &[Variant]) -> Result<(), Failure> {
+    let mut rng = Rng::new(seed);
+
+    for iteration in 0..iterations {
+        let size = 1 + rng.below(40);
+        let case = generate(&mut rng, size);
+
+        if let Err(mismatch) = check(&case, &REFERENCE, variants) {
+            let failing = |candidate: &Case| {
+                check(candidate, &REFERENCE, variants)
+                    .is_err_and(|other| other.variant == mismatch.variant)
+            };
+            let case = minimize(&case, failing);
+            let mismatch = check(&case, &REFERENCE, variants).unwrap_err();
+
+            return Err(Failure {
+                seed,
+                iteration,
+                case,
+                mismatch,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// An error found while reading a case, `line` starts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCaseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseCaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseCaseError {}
+
+fn join(words: &[isize]) -> String {
+    let words: Vec<_> = words.iter().map(ToString::to_string).collect();
+
+    words.join(",")
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "program {}", join(&self.program))?;
+        writeln!(f, "inputs {}", join(&self.inputs))
+    }
+}
+
+impl FromStr for Case {
+    type Err = ParseCaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<_> = s.lines().collect();
+
+        let field = |line: usize, name: &str| -> Result<Vec<isize>, ParseCaseError> {
+            let error = |message: String| ParseCaseError {
+                line: line + 1,
+                message,
+            };
+            let text = lines
+                .get(line)
+                .ok_or_else(|| error(format!("Expected `{}`", name)))?
+                .trim();
+            let mut parts = text.splitn(2, ' ');
+
+            if parts.next() != Some(name) {
+                return Err(error(format!("Expected `{}`", name)));
+            }
+
+            parts
+                .next()
+                .unwrap_or("")
+                .split(',')
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(|word| {
+                    word.parse()
+                        .map_err(|_| error(format!("Invalid value `{}`", word)))
+                })
+                .collect()
+        };
+
+        if lines.first().map(|line| line.trim()) != Some(HEADER) {
+            return Err(ParseCaseError {
+                line: 1,
+                message: format!("Expected `{}`", HEADER),
+            });
+        }
+
+        Ok(Self {
+            program: field(1, "program")?,
+            inputs: field(2, "inputs")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{check, fuzz, generate, minimize, variants, Case, Rng, Variant, REFERENCE};
+
+    #[test]
+    fn test_variants_agree() {
+        if let Err(failure) = fuzz(2019, 500, &variants()) {
+            panic!(
+                "Iteration {} of seed {} fails\n{}{}",
+                failure.iteration, failure.seed, failure.case, failure.mismatch
+            );
+        }
+    }
+
+    #[test]
+    fn test_generated_programs_run() {
+        let mut rng = Rng::new(7);
+        let mut halted = 0;
+
+        for _ in 0..100 {
+            let case = generate(&mut rng, 20);
+            let outcome = (REFERENCE.run)(&case);
+
+            if outcome.snapshot.halted {
+                halted += 1;
+            }
+        }
+
+        // Most programs should get somewhere rather than fault right away
+        assert!(halted > 25, "Only {} programs halted", halted);
+    }
+
+    #[test]
+    fn test_mismatches_are_minimized() {
+        // Doubles negative inputs before running the case
+        let broken = Variant {
+            name: "broken",
+            run: |case| {
+                let mut case = case.clone();
+                for input in &mut case.inputs {
+                    if *input < 0 {
+                        *input *= 2;
+                    }
+                }
+
+                (REFERENCE.run)(&case)
+            },
+        };
+        // Outputs every input doubled
+        let case = Case {
+            program: vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0],
+            inputs: vec![1, 2, -3, 4],
+        };
+
+        let mismatch = check(&case, &REFERENCE, &[broken]).unwrap_err();
+        assert_eq!(mismatch.variant, "broken");
+
+        let minimized = minimize(&case, |case| check(case, &REFERENCE, &[broken]).is_err());
+        assert_eq!(minimized.inputs, vec![-3]);
+        assert!(minimized.program.is_empty());
+        assert_eq!(minimized.to_string().parse(), Ok(minimized.clone()));
+    }
+
+    #[test]
+    fn test_regressions() {
+        let fixtures: Vec<_> = fs::read_dir("fuzz")
+            .expect("The regression fixtures are in `fuzz/`")
+            .map(|fixture| fixture.unwrap().path())
+            .collect();
+        assert!(!fixtures.is_empty(), "`fuzz/` has no regression fixtures");
+
+        for path in fixtures {
+            let case: Case = fs::read_to_string(&path).unwrap().parse().unwrap();
+
+            if let Err(mismatch) = check(&case, &REFERENCE, &variants()) {
+                panic!("{} fails\n{}", path.display(), mismatch);
+            }
+        }
+    }
+}
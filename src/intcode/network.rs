@@ -0,0 +1,431 @@
+//! Several [`Computer`]s exchanging values, scheduled deterministically.
+//!
+//! Machines are wired either by named channels, which deliver every output of one machine to the
+//! input of another, or by packets, where outputs are grouped into fixed size packets whose first
+//! word is the address of the receiving machine. Packets addressed to anything other than a machine
+//! in the network are kept until taken with [`Network::take_undelivered`].
+//!
+//! Each [`Network::step`] is a round in which every machine that hasn't halted, in the order they
+//! were added, runs until it halts or needs input. A round where no machine had queued input and
+//! no packets were sent is quiet, and the network is idle after [`DEFAULT_IDLE_ROUNDS`] quiet
+//! rounds in a row. A single quiet round isn't enough, a machine may poll several times before it
+//! sends anything.
+use std::collections::BTreeMap;
+
+use crate::intcode_computer::{Computer, IntcodeError, Memory, PagedMemory};
+
+/// Number of quiet rounds in a row after which a network is idle, unless set otherwise with
+/// [`Network::set_idle_rounds`].
+pub const DEFAULT_IDLE_ROUNDS: usize = 3;
+
+/// Index of a machine in a [`Network`], in the order machines were added.
+pub type MachineId = usize;
+
+/// Values sent from one machine to an address, which is a machine in the network or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub from: MachineId,
+    pub to: usize,
+    pub values: Vec<isize>,
+}
+
+/// A named connection from the outputs of one machine to the input of another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub from: MachineId,
+    pub to: MachineId,
+    /// The most recent value sent over the channel.
+    pub last: Option<isize>,
+    /// Number of values sent over the channel.
+    pub sent: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NetworkState {
+    /// Every machine has halted.
+    Halted,
+    /// Enough rounds passed in a row without queued input or any packets being sent.
+    Idle,
+}
+
+/// How outputs are turned into packets.
+enum Routing {
+    Channels(BTreeMap<String, Channel>),
+    /// Every `size` outputs of a machine form a packet, addressed by the first of them.
+    Packets(usize),
+}
+
+type Observer = Box<dyn FnMut(&Packet)>;
+
+pub struct Network<M: Memory<Word = isize> = PagedMemory> {
+    machines: Vec<Computer<M>>,
+    routing: Routing,
+    /// Outputs of each machine that don't form a complete packet yet.
+    partial: Vec<Vec<isize>>,
+    idle_input: Option<isize>,
+    idle_rounds: usize,
+    /// Number of quiet rounds since the last round with input or packets.
+    quiet_rounds: usize,
+    observer: Option<Observer>,
+    undelivered: Vec<Packet>,
+}
+
+impl<M: Memory<Word = isize>> Default for Network<M> {
+    fn default() -> Self {
+        Self {
+            machines: vec![],
+            routing: Routing::Channels(BTreeMap::new()),
+            partial: vec![],
+            idle_input: None,
+            idle_rounds: DEFAULT_IDLE_ROUNDS,
+            quiet_rounds: 0,
+            observer: None,
+            undelivered: vec![],
+        }
+    }
+}
+
+impl<M: Memory<Word = isize>> Network<M> {
+    /// Create an empty network that routes outputs over channels.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, computer: Computer<M>) -> MachineId {
+        self.machines.push(computer);
+        self.partial.push(vec![]);
+
+        self.machines.len() - 1
+    }
+
+    pub fn machine(&self, id: MachineId) -> &Computer<M> {
+        &self.machines[id]
+    }
+
+    pub fn machine_mut(&mut self, id: MachineId) -> &mut Computer<M> {
+        &mut self.machines[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    /// Deliver every output of `from` to the input of `to`, replacing any channel called `name`.
+    ///
+    /// Outputs of machines without channels stay in the machine's outputs. Panics if the network
+    /// routes packets.
+    pub fn connect(&mut self, name: &str, from: MachineId, to: MachineId) {
+        match &mut self.routing {
+            Routing::Channels(channels) => {
+                channels.insert(
+                    name.to_string(),
+                    Channel {
+                        from,
+                        to,
+                        last: None,
+                        sent: 0,
+                    },
+                );
+            }
+            Routing::Packets(_) => panic!("Channels can't be used with packet routing"),
+        }
+    }
+
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        match &self.routing {
+            Routing::Channels(channels) => channels.get(name),
+            Routing::Packets(_) => None,
+        }
+    }
+
+    /// Route outputs as packets of `size` words instead of over channels, removing all channels.
+    ///
+    /// The first word of a packet is the address it's sent to, the rest is delivered to the input
+    /// of that machine.
+    pub fn set_packet_routing(&mut self, size: usize) {
+        assert!(size > 0, "Packets need at least an address");
+
+        self.routing = Routing::Packets(size);
+    }
+
+    /// A value given to machines that start a round without input, `-1` for a network that polls.
+    pub fn set_idle_input(&mut self, value: Option<isize>) {
+        self.idle_input = value;
+    }
+
+    /// The number of quiet rounds in a row after which the network is idle.
+    pub fn set_idle_rounds(&mut self, rounds: usize) {
+        assert!(
+            rounds > 0,
+            "A network is only idle after at least one quiet round"
+        );
+
+        self.idle_rounds = rounds;
+    }
+
+    /// Call `observer` with every packet that's sent, including values sent over channels.
+    pub fn set_observer<F>(&mut self, observer: F)
+    where
+        F: FnMut(&Packet) + 'static,
+    {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Deliver `values` to the input of machine `to` from outside the network.
+    pub fn send(&mut self, to: MachineId, values: &[isize]) {
+        self.machines[to].push_inputs(values.iter().cloned());
+    }
+
+    /// Take the packets sent to addresses outside the network, oldest first.
+    pub fn take_undelivered(&mut self) -> Vec<Packet> {
+        std::mem::take(&mut self.undelivered)
+    }
+
+    /// Run a single round, returning the state of the network after it.
+    ///
+    /// Returns `None` if some machine can still make progress.
+    pub fn step(&mut self) -> Result<Option<NetworkState>, IntcodeError> {
+        let mut had_input = false;
+        let mut sent = 0;
+
+        for id in 0..self.machines.len() {
+            if self.machines[id].is_halted() {
+                continue;
+            }
+
+            if self.machines[id].pending_inputs() > 0 {
+                had_input = true;
+            } else if let Some(value) = self.idle_input {
+                self.machines[id].push_input(value);
+            }
+
+            self.machines[id].try_run(false)?;
+            sent += self.route(id);
+        }
+
+        if had_input || sent > 0 {
+            self.quiet_rounds = 0;
+        } else {
+            self.quiet_rounds += 1;
+        }
+
+        if self.machines.iter().all(Computer::is_halted) {
+            Ok(Some(NetworkState::Halted))
+        } else if self.quiet_rounds >= self.idle_rounds {
+            Ok(Some(NetworkState::Idle))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Run rounds until every machine halts or the network is idle.
+    pub fn run(&mut self) -> Result<NetworkState, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    /// Route the outputs of machine `from`, returning the number of packets sent.
+    fn route(&mut self, from: MachineId) -> usize {
+        let mut packets = vec![];
+
+        match &mut self.routing {
+            Routing::Channels(channels) => {
+                let mut outgoing: Vec<_> = channels
+                    .values_mut()
+                    .filter(|channel| channel.from == from)
+                    .collect();
+
+                if outgoing.is_empty() {
+                    return 0;
+                }
+
+                for value in self.machines[from].drain_outputs() {
+                    for channel in &mut outgoing {
+                        channel.last = Some(value);
+                        channel.sent += 1;
+                        packets.push(Packet {
+                            from,
+                            to: channel.to,
+                            values: vec![value],
+                        });
+                    }
+                }
+            }
+            Routing::Packets(size) => {
+                let partial = &mut self.partial[from];
+                partial.extend(self.machines[from].drain_outputs());
+
+                for words in partial.chunks_exact(*size) {
+                    packets.push(Packet {
+                        from,
+                        to: words[0] as usize,
+                        values: words[1..].to_vec(),
+                    });
+                }
+
+                let complete = partial.len() - partial.len() % *size;
+                partial.drain(..complete);
+            }
+        }
+
+        let sent = packets.len();
+        for packet in packets {
+            if let Some(observer) = self.observer.as_mut() {
+                observer(&packet);
+            }
+
+            if packet.to < self.machines.len() {
+                self.machines[packet.to].push_inputs(packet.values);
+            } else {
+                self.undelivered.push(packet);
+            }
+        }
+
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{Network, NetworkState, Packet, DEFAULT_IDLE_ROUNDS};
+    use crate::intcode::asm::assemble;
+    use crate::intcode_computer::Computer;
+
+    // Outputs every input plus one
+    const INCREMENT: [isize; 12] = [3, 11, 1001, 11, 1, 11, 4, 11, 1105, 1, 0, 0];
+
+    fn increment() -> Computer {
+        Computer::new(INCREMENT.to_vec())
+    }
+
+    #[test]
+    fn test_channels() {
+        let mut network = Network::new();
+        let first = network.add(increment());
+        let second = network.add(increment());
+        network.connect("first->second", first, second);
+        network.connect("second->first", second, first);
+        network.send(first, &[0]);
+
+        let packets = Rc::new(RefCell::new(vec![]));
+        let observed = Rc::clone(&packets);
+        network.set_observer(move |packet: &Packet| observed.borrow_mut().push(packet.clone()));
+
+        for _ in 0..3 {
+            assert_eq!(network.step(), Ok(None));
+        }
+
+        let channel = network.channel("second->first").unwrap();
+        assert_eq!((channel.last, channel.sent), (Some(6), 3));
+        assert_eq!(network.channel("first->second").unwrap().last, Some(5));
+        assert_eq!(packets.borrow().len(), 6);
+        assert_eq!(
+            packets.borrow()[0],
+            Packet {
+                from: 0,
+                to: 1,
+                values: vec![1]
+            }
+        );
+
+        // Without any input in flight the network goes idle
+        let mut network = Network::new();
+        network.add(increment());
+        assert_eq!(network.run(), Ok(NetworkState::Idle));
+    }
+
+    #[test]
+    fn test_packets() {
+        // Sends its second input plus one to the address in the first input, then halts
+        let program = assemble(
+            "in [to]\n\
+             in [value]\n\
+             add [value], #1, [value]\n\
+             out [to]\n\
+             out [value]\n\
+             hlt\n\
+             value: .data 0\n\
+             to: .data 0",
+        )
+        .unwrap();
+
+        let mut network = Network::new();
+        network.set_packet_routing(2);
+        for to in &[1, 7] {
+            let mut computer = Computer::new(program.clone());
+            computer.push_input(*to);
+            network.add(computer);
+        }
+        network.send(0, &[10]);
+
+        assert_eq!(network.run(), Ok(NetworkState::Halted));
+        assert_eq!(
+            network.take_undelivered(),
+            vec![Packet {
+                from: 1,
+                to: 7,
+                values: vec![12]
+            }]
+        );
+        assert!(network.take_undelivered().is_empty());
+    }
+
+    #[test]
+    fn test_idle_after_quiet_rounds() {
+        // Polls three times before it sends a packet to the NAT, then keeps polling
+        let program = assemble(
+            "poll:   in [x]\n\
+                     add [polls], #1, [polls]\n\
+                     lt [polls], #3, [t]\n\
+                     jnz [t], #poll\n\
+                     eq [polls], #3, [t]\n\
+                     jz [t], #poll\n\
+                     out #255\n\
+                     out [polls]\n\
+                     out #7\n\
+                     jnz #1, #poll\n\
+             x:      .data 0\n\
+             polls:  .data 0\n\
+             t:      .data 0",
+        )
+        .unwrap();
+        let nat_network = |idle_rounds| {
+            let mut network = Network::new();
+            network.set_packet_routing(3);
+            network.set_idle_input(Some(-1));
+            network.set_idle_rounds(idle_rounds);
+            network.add(Computer::new(program.clone()));
+
+            network
+        };
+
+        let mut network = nat_network(DEFAULT_IDLE_ROUNDS);
+        for _ in 0..2 {
+            assert_eq!(network.step(), Ok(None));
+        }
+        assert_eq!(network.run(), Ok(NetworkState::Idle));
+        assert_eq!(
+            network.take_undelivered(),
+            vec![Packet {
+                from: 0,
+                to: 255,
+                values: vec![3, 7]
+            }]
+        );
+
+        // Going idle after a single quiet round misses the packet
+        let mut network = nat_network(1);
+        assert_eq!(network.run(), Ok(NetworkState::Idle));
+        assert!(network.take_undelivered().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! Reading the line based text formats of [`snapshot`](super::snapshot),
+//! [`record`](super::record) and [`patch`](super::patch).
+//!
+//! Each format starts with a `intcode-<kind> <version>` header, followed by lines made of a name
+//! and a value separated by a space.
+use std::error::Error;
+use std::fmt;
+use std::iter::Enumerate;
+use std::str::{self, FromStr};
+
+/// An error found while reading one of the text formats, `line` starts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// The numbered lines of a text, read from the header onwards.
+pub(crate) struct Lines<'a> {
+    lines: Enumerate<str::Lines<'a>>,
+    count: usize,
+}
+
+impl<'a> Lines<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Self {
+            lines: text.lines().enumerate(),
+            count: text.lines().count(),
+        }
+    }
+
+    /// Read the `intcode-<kind> <version>` header, only the version in `header` is supported.
+    pub(crate) fn header(&mut self, header: &str) -> Result<(), ParseError> {
+        let (name, version) = header.split_once(' ').expect("Headers have a version");
+        let (line, found) = self.field(name)?;
+
+        if found != version {
+            return Err(ParseError::new(
+                line,
+                format!("Unsupported version `{}`", found),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Read the next line, which has to start with `name`, and return its number and value.
+    pub(crate) fn field(&mut self, name: &str) -> Result<(usize, &'a str), ParseError> {
+        let expected = || format!("Expected `{}`", name);
+        let (line, text) = self
+            .lines
+            .next()
+            .map(|(idx, text)| (idx + 1, text))
+            .ok_or_else(|| ParseError::new(self.count + 1, expected()))?;
+        let mut parts = text.trim().splitn(2, ' ');
+
+        if parts.next() != Some(name) {
+            return Err(ParseError::new(line, expected()));
+        }
+
+        Ok((line, parts.next().unwrap_or("").trim()))
+    }
+
+    /// The remaining lines that aren't blank, trimmed and with their numbers.
+    pub(crate) fn remaining(self) -> impl Iterator<Item = (usize, &'a str)> {
+        self.lines
+            .map(|(idx, text)| (idx + 1, text.trim()))
+            .filter(|(_, text)| !text.is_empty())
+    }
+}
+
+/// Parse `text` found on `line`.
+pub(crate) fn value<T: FromStr>(line: usize, text: &str) -> Result<T, ParseError> {
+    text.parse()
+        .map_err(|_| ParseError::new(line, format!("Invalid value `{}`", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{value, Lines, ParseError};
+
+    #[test]
+    fn test_lines() {
+        let mut lines = Lines::new("intcode-test 1\nsize  12 \n\n other a b\n");
+
+        assert_eq!(lines.header("intcode-test 1"), Ok(()));
+        assert_eq!(lines.field("size"), Ok((2, "12")));
+        assert_eq!(value::<usize>(2, "12"), Ok(12));
+        assert_eq!(
+            lines.remaining().collect::<Vec<_>>(),
+            vec![(4, "other a b")]
+        );
+
+        assert_eq!(
+            Lines::new("intcode-test 2\n").header("intcode-test 1"),
+            Err(ParseError::new(1, "Unsupported version `2`"))
+        );
+        assert_eq!(
+            Lines::new("").field("size"),
+            Err(ParseError::new(1, "Expected `size`"))
+        );
+        assert_eq!(
+            value::<isize>(3, "x"),
+            Err(ParseError::new(3, "Invalid value `x`"))
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Named sets of words to write over a program, for game modes, inputs baked into the code and
+//! cheats.
+//!
+//! A [`PatchSet`] is written like the other text formats, with a header, an optional name and a
+//! patch per line. Later patches to the same address win:
+//!
+//! ```text
+//! intcode-patches 1
+//! name Free play
+//! patch 0=2
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use crate::intcode::parse::{Lines, ParseError};
+use crate::intcode_computer::{IntcodeErrorReason, Memory};
+
+const HEADER: &str = "intcode-patches 1";
+
+/// The highest address a patch may write to, patches are applied to plain vectors of words.
+pub const MAX_ADDRESS: usize = 1 << 20;
+
+/// `value` written to `address`, written as `address=value`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Patch {
+    pub address: usize,
+    pub value: isize,
+}
+
+impl Patch {
+    pub fn new(address: usize, value: isize) -> Self {
+        Self { address, value }
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.address, self.value)
+    }
+}
+
+impl FromStr for Patch {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        let address = parts.next().and_then(|address| address.trim().parse().ok());
+        let value = parts.next().and_then(|value| value.trim().parse().ok());
+
+        match (address, value) {
+            (Some(address), Some(_)) if address > MAX_ADDRESS => Err(ParseError::new(
+                1,
+                format!(
+                    "Patch address {} is past the highest patchable address {}",
+                    address, MAX_ADDRESS
+                ),
+            )),
+            (Some(address), Some(value)) => Ok(Self::new(address, value)),
+            _ => Err(ParseError::new(
+                1,
+                format!("Invalid patch `{}`, expected `<address>=<value>`", s.trim()),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchSet {
+    pub name: Option<String>,
+    patches: Vec<Patch>,
+}
+
+impl PatchSet {
+    pub fn new(patches: Vec<Patch>) -> Self {
+        Self {
+            name: None,
+            patches,
+        }
+    }
+
+    pub fn named(name: &str, patches: Vec<Patch>) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            patches,
+        }
+    }
+
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    pub fn push(&mut self, patch: Patch) {
+        self.patches.push(patch);
+    }
+
+    /// The patches of both sets, with the ones of `other` applied last. Keeps the name of `self`.
+    pub fn combine(&self, other: &Self) -> Self {
+        let mut combined = self.clone();
+        combined.patches.extend_from_slice(&other.patches);
+
+        combined
+    }
+
+    /// Take the patches of `other` out again, the last equal patch for each of them.
+    pub fn remove(&mut self, other: &Self) {
+        for patch in &other.patches {
+            if let Some(index) = self.patches.iter().rposition(|p| p == patch) {
+                self.patches.remove(index);
+            }
+        }
+    }
+
+    /// Patch `words`, growing them for patches past their end. Fails without changing `words` if
+    /// a patch is past [`MAX_ADDRESS`].
+    pub fn apply_to(&self, words: &mut Vec<isize>) -> Result<(), IntcodeErrorReason> {
+        if let Some(patch) = self.patches.iter().find(|p| p.address > MAX_ADDRESS) {
+            return Err(IntcodeErrorReason::MemoryLimitExceeded(patch.address));
+        }
+
+        for patch in &self.patches {
+            if patch.address >= words.len() {
+                words.resize(patch.address + 1, 0);
+            }
+            words[patch.address] = patch.value;
+        }
+
+        Ok(())
+    }
+
+    /// Patch the memory of a running program. Returns the patches that revert this, which
+    /// write back the words as they were.
+    pub fn apply<M>(&self, memory: &mut M) -> Result<Self, IntcodeErrorReason>
+    where
+        M: Memory<Word = isize>,
+    {
+        let mut revert = Self {
+            name: self.name.as_ref().map(|name| format!("revert {}", name)),
+            patches: vec![],
+        };
+
+        for patch in &self.patches {
+            let original = Patch::new(patch.address, memory.read(patch.address));
+
+            if let Err(reason) = memory.write(patch.address, patch.value) {
+                // Leave the memory as it was
+                revert.apply(memory)?;
+                return Err(reason);
+            }
+            revert.patches.insert(0, original);
+        }
+
+        Ok(revert)
+    }
+
+    /// The patch lines of the text format, for embedding them in other formats.
+    pub(crate) fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.patches.iter().map(|patch| format!("patch {}", patch))
+    }
+}
+
+impl fmt::Display for PatchSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        if let Some(name) = &self.name {
+            writeln!(f, "name {}", name)?;
+        }
+
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for PatchSet {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = Lines::new(s);
+        lines.header(HEADER)?;
+        let mut set = Self::default();
+
+        for (line, text) in lines.remaining() {
+            match text.splitn(2, ' ').collect::<Vec<_>>()[..] {
+                ["patch", patch] => set.push(
+                    patch
+                        .parse()
+                        .map_err(|error: ParseError| ParseError { line, ..error })?,
+                ),
+                ["name", name] if set.name.is_none() => set.name = Some(name.trim().to_string()),
+                _ => return Err(ParseError::new(line, format!("Unexpected line `{}`", text))),
+            }
+        }
+
+        Ok(set)
+    }
+}
+
+impl Extend<Patch> for PatchSet {
+    fn extend<I: IntoIterator<Item = Patch>>(&mut self, iter: I) {
+        self.patches.extend(iter);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Patch, PatchSet, MAX_ADDRESS};
+    use crate::intcode::parse::ParseError;
+    use crate::intcode_computer::{IntcodeErrorReason, Memory, PagedMemory};
+
+    #[test]
+    fn test_format() {
+        let text = "intcode-patches 1\nname Gravity assist\npatch 1=12\npatch 2=2\n";
+        let set: PatchSet = text.parse().unwrap();
+
+        assert_eq!(set.name.as_deref(), Some("Gravity assist"));
+        assert_eq!(set.patches(), &[Patch::new(1, 12), Patch::new(2, 2)]);
+        assert_eq!(set.to_string(), text);
+
+        assert_eq!(" 3 = -4 ".parse(), Ok(Patch::new(3, -4)));
+        assert_eq!(
+            "intcode-patches 1\npatch 1=12\npatch 2\n".parse::<PatchSet>(),
+            Err(ParseError {
+                line: 3,
+                message: "Invalid patch `2`, expected `<address>=<value>`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-patches 2\npatch 1=12\n".parse::<PatchSet>(),
+            Err(ParseError {
+                line: 1,
+                message: "Unsupported version `2`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-patches 1\nname A\nname B\n".parse::<PatchSet>(),
+            Err(ParseError {
+                line: 3,
+                message: "Unexpected line `name B`".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_combine_and_remove() {
+        let noun_and_verb = PatchSet::new(vec![Patch::new(1, 12), Patch::new(2, 2)]);
+        let verb = PatchSet::new(vec![Patch::new(2, 5)]);
+
+        let mut combined = noun_and_verb.combine(&verb);
+        let mut words = vec![1, 0, 0, 0, 99];
+        combined.apply_to(&mut words).unwrap();
+        assert_eq!(words, vec![1, 12, 5, 0, 99]);
+
+        combined.remove(&verb);
+        assert_eq!(combined, noun_and_verb);
+
+        let mut words = vec![99];
+        PatchSet::new(vec![Patch::new(3, 7)])
+            .apply_to(&mut words)
+            .unwrap();
+        assert_eq!(words, vec![99, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_huge_addresses() {
+        for &address in &[MAX_ADDRESS + 1, 100000000000000, usize::MAX] {
+            let set = PatchSet::new(vec![Patch::new(0, 2), Patch::new(address, 1)]);
+            let mut words = vec![1, 0, 0, 0, 99];
+
+            assert_eq!(
+                set.apply_to(&mut words),
+                Err(IntcodeErrorReason::MemoryLimitExceeded(address))
+            );
+            assert_eq!(words, vec![1, 0, 0, 0, 99]);
+
+            assert_eq!(
+                format!("{}=1", address).parse::<Patch>(),
+                Err(ParseError {
+                    line: 1,
+                    message: format!(
+                        "Patch address {} is past the highest patchable address {}",
+                        address, MAX_ADDRESS
+                    )
+                })
+            );
+        }
+
+        let mut words = vec![];
+        PatchSet::new(vec![Patch::new(MAX_ADDRESS, 1)])
+            .apply_to(&mut words)
+            .unwrap();
+        assert_eq!(words.len(), MAX_ADDRESS + 1);
+    }
+
+    #[test]
+    fn test_apply_and_revert() {
+        let mut memory = PagedMemory::new(&[1, 9, 10, 3, 99]);
+        let cheat = PatchSet::named("cheat", vec![Patch::new(1, 5), Patch::new(1, 6)]);
+
+        let revert = cheat.apply(&mut memory).unwrap();
+        assert_eq!(memory.read(1), 6);
+        assert_eq!(revert.name.as_deref(), Some("revert cheat"));
+
+        revert.apply(&mut memory).unwrap();
+        assert_eq!(memory.read(1), 9);
+    }
+}
@@ -0,0 +1,422 @@
+//! Loading Intcode programs from text or a compact binary format.
+//!
+//! Plain programs are numbers separated by commas or newlines, like the puzzle inputs. A program
+//! can carry metadata in a header, naming it and patching words before it runs:
+//!
+//! ```text
+//! intcode-program 1
+//! name Arcade cabinet
+//! patch 0=2
+//! 1,380,379,385,1008,2267,...
+//! ```
+//!
+//! The binary format starts with [`MAGIC`], followed by the length of the metadata as a little
+//! endian `u64`, the metadata lines as text and the words as little endian `i64`s.
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::{self, FromStr};
+
+use crate::intcode::parse::ParseError;
+use crate::intcode::patch::{Patch, PatchSet};
+
+const HEADER: &str = "intcode-program 1";
+
+/// The first bytes of a binary program.
+pub const MAGIC: &[u8; 8] = b"\0intcode";
+
+const WORD_SIZE: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub name: Option<String>,
+    /// Words written over the code before it runs.
+    pub patches: PatchSet,
+    pub code: Vec<isize>,
+}
+
+/// An error found while loading a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseProgramError {
+    /// A metadata line is invalid, `line` starts from 1 at the header.
+    Header { line: usize, message: String },
+    /// The word with the zero based `index` is invalid.
+    Word { index: usize, message: String },
+    /// There are no words at all.
+    Empty,
+    /// The input is neither text nor a binary program.
+    Encoding,
+    /// The file couldn't be read.
+    Io(String),
+}
+
+impl fmt::Display for ParseProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header { line, message } => write!(f, "line {}: {}", line, message),
+            Self::Word { index, message } => write!(f, "word {}: {}", index, message),
+            Self::Empty => write!(f, "The program has no words"),
+            Self::Encoding => write!(f, "Expected text or a binary program"),
+            Self::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ParseProgramError {}
+
+impl Program {
+    pub fn new(code: Vec<isize>) -> Self {
+        Self {
+            code,
+            ..Self::default()
+        }
+    }
+
+    /// The code with the patches applied, ready to be given to a
+    /// [`Computer`](crate::intcode_computer::Computer).
+    ///
+    /// Panics if a patch past [`MAX_ADDRESS`](crate::intcode::patch::MAX_ADDRESS) was added by
+    /// hand, loading a program rejects those.
+    pub fn words(&self) -> Vec<isize> {
+        let mut words = self.code.clone();
+        self.patches
+            .apply_to(&mut words)
+            .expect("Patches should be within the patchable addresses");
+
+        words
+    }
+
+    /// The program with `patches` applied after its own.
+    pub fn patched(&self, patches: &PatchSet) -> Self {
+        let mut program = self.clone();
+        program.apply(patches);
+
+        program
+    }
+
+    pub fn apply(&mut self, patches: &PatchSet) {
+        self.patches = self.patches.combine(patches);
+    }
+
+    /// Undo [`apply`](Self::apply), the words `patches` wrote go back to what they were before.
+    pub fn revert(&mut self, patches: &PatchSet) {
+        self.patches.remove(patches);
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseProgramError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|error| {
+            ParseProgramError::Io(format!("Failed to read {}: {}", path.display(), error))
+        })?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Load a program in either format, binary if it starts with [`MAGIC`] and text otherwise.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseProgramError> {
+        if !bytes.starts_with(MAGIC) {
+            return str::from_utf8(bytes)
+                .map_err(|_| ParseProgramError::Encoding)?
+                .parse();
+        }
+
+        let bytes = &bytes[MAGIC.len()..];
+        let truncated = || ParseProgramError::Header {
+            line: 1,
+            message: "Truncated metadata".to_string(),
+        };
+
+        let length = bytes.get(..WORD_SIZE).ok_or_else(truncated)?;
+        let length = u64::from_le_bytes(<[u8; WORD_SIZE]>::try_from(length).unwrap());
+        let length = usize::try_from(length).map_err(|_| truncated())?;
+        let metadata = bytes
+            .get(WORD_SIZE..WORD_SIZE.saturating_add(length))
+            .ok_or_else(truncated)?;
+        let metadata = str::from_utf8(metadata).map_err(|_| ParseProgramError::Encoding)?;
+
+        let mut program = Self::default();
+        program.parse_metadata(metadata.lines())?;
+
+        let words = &bytes[WORD_SIZE + length..];
+        program.code = words
+            .chunks(WORD_SIZE)
+            .enumerate()
+            .map(|(index, word)| {
+                let word =
+                    <[u8; WORD_SIZE]>::try_from(word).map_err(|_| ParseProgramError::Word {
+                        index,
+                        message: format!("Expected {} bytes, found {}", WORD_SIZE, word.len()),
+                    })?;
+                let word = i64::from_le_bytes(word);
+
+                isize::try_from(word).map_err(|_| ParseProgramError::Word {
+                    index,
+                    message: format!("`{}` doesn't fit in a word", word),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        program.validate()
+    }
+
+    /// The program in the binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let metadata = self.metadata();
+        let mut bytes = MAGIC.to_vec();
+
+        bytes.extend_from_slice(&(metadata.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(metadata.as_bytes());
+        for &word in &self.code {
+            bytes.extend_from_slice(&(word as i64).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// The metadata lines, without the header.
+    fn metadata(&self) -> String {
+        let mut metadata = String::new();
+
+        if let Some(name) = &self.name {
+            metadata.push_str(&format!("name {}\n", name));
+        }
+        for line in self.patches.lines() {
+            metadata.push_str(&line);
+            metadata.push('\n');
+        }
+
+        metadata
+    }
+
+    /// Read metadata lines, numbered from 2 after the header, until the first line that isn't
+    /// metadata. Returns the number of lines read.
+    fn parse_metadata<'a, I>(&mut self, lines: I) -> Result<usize, ParseProgramError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut count = 0;
+
+        for (number, line) in lines.enumerate().map(|(idx, line)| (idx + 2, line)) {
+            let mut parts = line.trim().splitn(2, ' ');
+            let (key, value) = (parts.next().unwrap(), parts.next().unwrap_or("").trim());
+            let error = |message: String| ParseProgramError::Header {
+                line: number,
+                message,
+            };
+
+            match key {
+                "name" if self.name.is_some() => return Err(error("Duplicate `name`".to_string())),
+                "name" => self.name = Some(value.to_string()),
+                "patch" => {
+                    let patch: Patch = value
+                        .parse()
+                        .map_err(|patch_error: ParseError| error(patch_error.message))?;
+
+                    self.patches.push(patch);
+                }
+                _ => break,
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn validate(self) -> Result<Self, ParseProgramError> {
+        if self.code.is_empty() {
+            return Err(ParseProgramError::Empty);
+        }
+
+        Ok(self)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = self.metadata();
+        if !metadata.is_empty() {
+            write!(f, "{}\n{}", HEADER, metadata)?;
+        }
+
+        let words: Vec<_> = self.code.iter().map(ToString::to_string).collect();
+        writeln!(f, "{}", words.join(","))
+    }
+}
+
+impl FromStr for Program {
+    type Err = ParseProgramError;
+
+    /// Parse a program with words separated by commas or whitespace, after an optional header.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut program = Self::default();
+        let mut code = s;
+
+        if s.lines().next().map(str::trim) == Some(HEADER) {
+            let lines = program.parse_metadata(s.lines().skip(1))?;
+            let offset: usize = s.split_inclusive('\n').take(lines + 1).map(str::len).sum();
+            code = &s[offset..];
+        }
+
+        // Words are separated by a comma or a run of whitespace, whitespace around a comma is part
+        // of the separator
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(ParseProgramError::Empty);
+        }
+
+        for field in code.split(',') {
+            let index = program.code.len();
+            if field.trim().is_empty() {
+                return Err(ParseProgramError::Word {
+                    index,
+                    message: "Expected a word between commas".to_string(),
+                });
+            }
+
+            for (index, token) in (index..).zip(field.split_whitespace()) {
+                let word = token.parse().map_err(|_| ParseProgramError::Word {
+                    index,
+                    message: format!("Invalid number `{}`", token),
+                })?;
+                program.code.push(word);
+            }
+        }
+
+        program.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseProgramError, Program, MAGIC};
+    use crate::intcode::patch::{Patch, PatchSet};
+
+    #[test]
+    fn test_text() {
+        let plain: Program = "1,9,10,3,\n2,3,11,0\n99\n30 40 50\n".parse().unwrap();
+        assert_eq!(
+            plain,
+            Program::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50])
+        );
+        assert_eq!(plain.to_string(), "1,9,10,3,2,3,11,0,99,30,40,50\n");
+
+        let text = "intcode-program 1\nname Arcade cabinet\npatch 0=2\npatch 3=-1\n1,2,3,4\n";
+        let program: Program = text.parse().unwrap();
+        assert_eq!(program.name.as_deref(), Some("Arcade cabinet"));
+        assert_eq!(
+            program.patches.patches(),
+            &[Patch::new(0, 2), Patch::new(3, -1)]
+        );
+        assert_eq!(program.code, vec![1, 2, 3, 4]);
+        assert_eq!(program.words(), vec![2, 2, 3, -1]);
+        assert_eq!(program.to_string(), text);
+    }
+
+    #[test]
+    fn test_binary() {
+        let program = Program {
+            name: Some("Day 2".to_string()),
+            patches: PatchSet::new(vec![Patch::new(1, 12), Patch::new(2, 2)]),
+            code: vec![1, 0, 0, 3, 1 << 40, -7, 99],
+        };
+
+        let bytes = program.to_bytes();
+        assert!(bytes.starts_with(MAGIC));
+        assert_eq!(Program::from_bytes(&bytes), Ok(program.clone()));
+
+        // Text goes through the same entry point
+        let text = program.to_string();
+        assert_eq!(Program::from_bytes(text.as_bytes()), Ok(program));
+
+        let plain = Program::new(vec![99]);
+        assert_eq!(plain.to_bytes().len(), MAGIC.len() + 8 + 8);
+        assert_eq!(Program::from_bytes(&plain.to_bytes()), Ok(plain));
+    }
+
+    #[test]
+    fn test_patches() {
+        let mut program = Program::new(vec![1, 0, 0, 0, 99]);
+        let noun_and_verb = PatchSet::named("1202", vec![Patch::new(1, 12), Patch::new(2, 2)]);
+        let cheat = PatchSet::new(vec![Patch::new(3, 7)]);
+
+        assert_eq!(
+            program.patched(&noun_and_verb).words(),
+            vec![1, 12, 2, 0, 99]
+        );
+
+        program.apply(&noun_and_verb);
+        program.apply(&cheat);
+        assert_eq!(program.words(), vec![1, 12, 2, 7, 99]);
+
+        program.revert(&noun_and_verb);
+        assert_eq!(program.words(), vec![1, 0, 0, 7, 99]);
+        assert_eq!(program.code, vec![1, 0, 0, 0, 99]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |index: usize, message: &str| ParseProgramError::Word {
+            index,
+            message: message.to_string(),
+        };
+
+        assert_eq!(
+            "1,2,,3,99".parse::<Program>(),
+            Err(error(2, "Expected a word between commas"))
+        );
+        assert_eq!(
+            "1,2,99,\n".parse::<Program>(),
+            Err(error(3, "Expected a word between commas"))
+        );
+        assert_eq!(
+            "1,2,3,x,4".parse::<Program>(),
+            Err(error(3, "Invalid number `x`"))
+        );
+        assert_eq!(
+            "1, 2\n3  x 5".parse::<Program>(),
+            Err(error(3, "Invalid number `x`"))
+        );
+        assert_eq!(
+            "intcode-program 1\nname Test\n1\n2,3.5".parse::<Program>(),
+            Err(error(2, "Invalid number `3.5`"))
+        );
+        assert_eq!(
+            "intcode-program 1\npatch 0:2\n99".parse::<Program>(),
+            Err(ParseProgramError::Header {
+                line: 2,
+                message: "Invalid patch `0:2`, expected `<address>=<value>`".to_string()
+            })
+        );
+        assert_eq!(" \n\n".parse::<Program>(), Err(ParseProgramError::Empty));
+        assert_eq!(
+            Program::from_bytes(&[0xff, 0xfe]),
+            Err(ParseProgramError::Encoding)
+        );
+
+        let huge = "intcode-program 1\npatch 100000000000000=1\n99";
+        let error_message = "Patch address 100000000000000 is past the highest patchable address";
+        let huge_binary = Program {
+            patches: PatchSet::new(vec![Patch::new(100000000000000, 1)]),
+            ..Program::new(vec![99])
+        }
+        .to_bytes();
+        for result in &[huge.parse::<Program>(), Program::from_bytes(&huge_binary)] {
+            match result {
+                Err(ParseProgramError::Header { line: 2, message }) => {
+                    assert!(message.starts_with(error_message), "{}", message)
+                }
+                result => panic!("Expected an error for the patch, got {:?}", result),
+            }
+        }
+
+        let mut bytes = Program::new(vec![1, 2]).to_bytes();
+        bytes.pop();
+        assert_eq!(
+            Program::from_bytes(&bytes),
+            Err(error(1, "Expected 8 bytes, found 7"))
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! Recorded Intcode I/O sessions, for pinning down the behavior of interactive programs.
+//!
+//! A [`Recorder`] is a [`Tracer`] that logs every input consumed and every output produced by the
+//! [`Computer`] it's attached to, together with the number of instructions executed before it.
+//! The resulting [`Recording`] is written to text with [`Display`](fmt::Display) and read back
+//! with [`FromStr`]:
+//!
+//! ```text
+//! intcode-recording 1
+//! steps 5
+//! halted true
+//! 0 in 4
+//! 1 in 5
+//! 3 out 9
+//! ```
+//!
+//! [`replay`] runs a program on the recorded inputs and checks that it behaves exactly as
+//! recorded, without having to repeat whatever decided on those inputs in the first place.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::intcode::parse::{value, Lines, ParseError};
+use crate::intcode_computer::{
+    Computer, ExecutionState, Instruction, IntcodeError, Memory, TraceEvent, Tracer, Word,
+};
+
+const HEADER: &str = "intcode-recording 1";
+
+/// A value that crossed the boundary of the computer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event<W: Word = isize> {
+    Input(W),
+    Output(W),
+}
+
+/// An [`Event`] and the number of instructions executed before the instruction that caused it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Entry<W: Word = isize> {
+    pub step: u64,
+    pub event: Event<W>,
+}
+
+/// Everything a program read and wrote during a session, see [`Recorder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording<W: Word = isize> {
+    /// Entries in the order they happened.
+    pub entries: Vec<Entry<W>>,
+    /// Number of instructions executed during the session, including the halt instruction.
+    pub steps: u64,
+    pub halted: bool,
+}
+
+impl<W: Word> Recording<W> {
+    /// The recorded inputs, in the order they were consumed.
+    pub fn inputs(&self) -> impl Iterator<Item = W> + '_ {
+        self.entries.iter().filter_map(|entry| match entry.event {
+            Event::Input(value) => Some(value),
+            Event::Output(_) => None,
+        })
+    }
+
+    /// The recorded outputs, in the order they were produced.
+    pub fn outputs(&self) -> impl Iterator<Item = W> + '_ {
+        self.entries.iter().filter_map(|entry| match entry.event {
+            Event::Input(_) => None,
+            Event::Output(value) => Some(value),
+        })
+    }
+}
+
+/// Records a session of the [`Computer`] it's attached to with [`Computer::set_tracer`].
+///
+/// Recording starts with the next executed instruction, so attach the recorder before running the
+/// program to record a whole session. Once the program halts nothing else is recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder<W: Word = isize> {
+    recording: Recording<W>,
+}
+
+impl<W: Word> Recorder<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The session recorded so far.
+    pub fn recording(&self) -> &Recording<W> {
+        &self.recording
+    }
+
+    pub fn into_recording(self) -> Recording<W> {
+        self.recording
+    }
+}
+
+impl<W: Word> Tracer<W> for Recorder<W> {
+    fn trace(&mut self, event: &TraceEvent<W>) {
+        let recording = &mut self.recording;
+
+        if recording.halted {
+            return;
+        }
+
+        let recorded = match event.instruction {
+            Instruction::Input(_) => event.written.map(|(_, value)| Event::Input(value)),
+            Instruction::Output(_) => event.reads[0].map(Event::Output),
+            Instruction::Halt => {
+                recording.halted = true;
+                None
+            }
+            _ => None,
+        };
+
+        if let Some(recorded) = recorded {
+            recording.entries.push(Entry {
+                step: recording.steps,
+                event: recorded,
+            });
+        }
+
+        recording.steps += 1;
+    }
+}
+
+/// How a replayed session differed from its recording.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError<W: Word = isize> {
+    /// The program faulted.
+    Fault(IntcodeError),
+    /// Entry `index` differs, `None` if the session has no entry at that index.
+    Diverged {
+        index: usize,
+        expected: Option<Entry<W>>,
+        actual: Option<Entry<W>>,
+    },
+    /// Every entry matched, but the session ran for a different number of steps or ended
+    /// differently.
+    Ended {
+        expected: Recording<W>,
+        actual: Recording<W>,
+    },
+}
+
+impl<W: Word> fmt::Display for ReplayError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn describe<W: Word>(entry: &Option<Entry<W>>) -> String {
+            match entry {
+                Some(Entry {
+                    step,
+                    event: Event::Input(value),
+                }) => format!("input {} at step {}", value, step),
+                Some(Entry {
+                    step,
+                    event: Event::Output(value),
+                }) => format!("output {} at step {}", value, step),
+                None => "nothing".to_string(),
+            }
+        }
+
+        match self {
+            ReplayError::Fault(error) => write!(f, "The replayed program faulted: {}", error),
+            ReplayError::Diverged {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Entry {} diverged, expected {} but got {}",
+                index,
+                describe(expected),
+                describe(actual)
+            ),
+            ReplayError::Ended { expected, actual } => write!(
+                f,
+                "Expected the session to end after {} steps with halted {}, but it ended after {} \
+                 steps with halted {}",
+                expected.steps, expected.halted, actual.steps, actual.halted
+            ),
+        }
+    }
+}
+
+impl<W: Word> Error for ReplayError<W> {}
+
+/// The first index from `checked` where the entries of `actual` and `expected` differ.
+fn diverged<W: Word>(
+    checked: usize,
+    expected: &[Entry<W>],
+    actual: &[Entry<W>],
+) -> Option<ReplayError<W>> {
+    (checked..actual.len().max(expected.len()))
+        .map(|index| (index, expected.get(index), actual.get(index)))
+        .find(|(_, expected, actual)| expected != actual)
+        .map(|(index, expected, actual)| ReplayError::Diverged {
+            index,
+            expected: expected.copied(),
+            actual: actual.copied(),
+        })
+}
+
+/// Run `computer` on the inputs of `recording` and check that it produces the same session.
+///
+/// The computer should be fresh from the program the recording was made with. Its tracer is
+/// replaced by a [`Recorder`] for the replay, which is detached again before returning. Outputs
+/// are compared as soon as they're produced, so a replay that diverged stops at the next output.
+///
+/// The replay ends where the session ended: when the program halts, when it needs more input than
+/// was recorded, or at the last output if the session was stopped right after it. It never runs
+/// more than one step past the recorded steps, so a replay that keeps going fails as ended.
+pub fn replay<M: Memory>(
+    computer: &mut Computer<M>,
+    recording: &Recording<M::Word>,
+) -> Result<(), ReplayError<M::Word>> {
+    computer.set_tracer(Recorder::<M::Word>::new());
+    computer.push_inputs(recording.inputs());
+
+    let mut checked = 0;
+    let mut replayed = 0;
+    let result = loop {
+        let budget = recording.steps.saturating_sub(replayed).saturating_add(1);
+        let state = match computer.run_for(budget, true) {
+            Ok(state) => state,
+            Err(error) => break Err(ReplayError::Fault(error)),
+        };
+
+        let actual = &computer
+            .tracer::<Recorder<M::Word>>()
+            .expect("The recorder is attached for the whole replay")
+            .recording;
+        replayed = actual.steps;
+
+        if let ExecutionState::Output(_) = state {
+            let so_far = actual.entries.len().min(recording.entries.len());
+            if let Some(error) = diverged(checked, &recording.entries[..so_far], &actual.entries) {
+                break Err(error);
+            }
+
+            checked = actual.entries.len();
+
+            // Sessions that stopped on an output end with that output
+            if actual.steps == recording.steps && checked == recording.entries.len() {
+                break Ok(());
+            }
+
+            continue;
+        }
+
+        if let Some(error) = diverged(checked, &recording.entries, &actual.entries) {
+            break Err(error);
+        }
+
+        if actual.steps != recording.steps || actual.halted != recording.halted {
+            break Err(ReplayError::Ended {
+                expected: recording.clone(),
+                actual: actual.clone(),
+            });
+        }
+
+        break Ok(());
+    };
+
+    computer.take_tracer::<Recorder<M::Word>>();
+
+    result
+}
+
+impl<W: Word> fmt::Display for Recording<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "steps {}", self.steps)?;
+        writeln!(f, "halted {}", self.halted)?;
+
+        for entry in &self.entries {
+            match entry.event {
+                Event::Input(value) => writeln!(f, "{} in {}", entry.step, value)?,
+                Event::Output(value) => writeln!(f, "{} out {}", entry.step, value)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Word> FromStr for Recording<W> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = Lines::new(s);
+        lines.header(HEADER)?;
+
+        let (line, steps) = lines.field("steps")?;
+        let steps = value(line, steps)?;
+        let (line, halted) = lines.field("halted")?;
+        let halted = value(line, halted)?;
+
+        let mut entries = vec![];
+        for (line, text) in lines.remaining() {
+            let mut parts = text.split_whitespace();
+            let step = value(line, parts.next().unwrap_or(""))?;
+            let kind = parts.next().unwrap_or("");
+            let recorded = value(line, parts.next().unwrap_or(""))?;
+
+            if let Some(extra) = parts.next() {
+                return Err(ParseError::new(line, format!("Unexpected `{}`", extra)));
+            }
+
+            let event = match kind {
+                "in" => Event::Input(recorded),
+                "out" => Event::Output(recorded),
+                _ => return Err(ParseError::new(line, "Expected `in` or `out`")),
+            };
+
+            entries.push(Entry { step, event });
+        }
+
+        Ok(Self {
+            entries,
+            steps,
+            halted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replay, Entry, Event, Recorder, Recording, ReplayError};
+    use crate::intcode::parse::ParseError;
+    use crate::intcode_computer::{Computer, ExecutionState};
+
+    // Reads two values and outputs their sum
+    const SUM: [isize; 11] = [3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99];
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut computer = Computer::new(SUM.to_vec());
+        computer.set_tracer(Recorder::new());
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::NeedsInput));
+        computer.push_inputs(vec![4, 5]);
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+
+        let recording: Recording = computer.take_tracer::<Recorder>().unwrap().into_recording();
+        let text = recording.to_string();
+        assert_eq!(
+            text,
+            "intcode-recording 1\n\
+             steps 5\n\
+             halted true\n\
+             0 in 4\n\
+             1 in 5\n\
+             3 out 9\n"
+        );
+        assert_eq!(text.parse(), Ok(recording.clone()));
+
+        assert_eq!(replay(&mut Computer::new(SUM.to_vec()), &recording), Ok(()));
+
+        // Outputs the product instead
+        let mut product = SUM.to_vec();
+        product[4] = 2;
+        assert_eq!(
+            replay(&mut Computer::new(product), &recording),
+            Err(ReplayError::Diverged {
+                index: 2,
+                expected: Some(Entry {
+                    step: 3,
+                    event: Event::Output(9)
+                }),
+                actual: Some(Entry {
+                    step: 3,
+                    event: Event::Output(20)
+                }),
+            })
+        );
+
+        // A session that was stopped after its output
+        let mut stopped = recording.clone();
+        stopped.halted = false;
+        stopped.steps = 4;
+        assert_eq!(replay(&mut Computer::new(SUM.to_vec()), &stopped), Ok(()));
+
+        let mut longer = recording.clone();
+        longer.steps = 6;
+        assert_eq!(
+            replay(&mut Computer::new(SUM.to_vec()), &longer),
+            Err(ReplayError::Ended {
+                expected: longer.clone(),
+                actual: recording,
+            })
+        );
+    }
+
+    #[test]
+    fn test_replay_step_budget() {
+        // Jumps back to itself forever
+        let program = vec![1105, 1, 0];
+        let recording = Recording {
+            entries: vec![],
+            steps: 10,
+            halted: true,
+        };
+
+        match replay(&mut Computer::new(program), &recording) {
+            Err(ReplayError::Ended { actual, .. }) => {
+                assert_eq!(actual.steps, 11);
+                assert!(!actual.halted);
+            }
+            result => panic!("Expected the replay to end, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "intcode-recording 2\n".parse::<Recording>(),
+            Err(ParseError {
+                line: 1,
+                message: "Unsupported version `2`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-recording 1\nsteps 4\nhalted true\n0 in 1\n2 put 3\n".parse::<Recording>(),
+            Err(ParseError {
+                line: 5,
+                message: "Expected `in` or `out`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-recording 1\nsteps 4\nhalted true\n0 in 1 2\n".parse::<Recording>(),
+            Err(ParseError {
+                line: 4,
+                message: "Unexpected `2`".to_string()
+            })
+        );
+    }
+}
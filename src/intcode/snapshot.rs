@@ -0,0 +1,201 @@
+//! Saved [`Computer`](crate::intcode_computer::Computer) state.
+//!
+//! A [`Snapshot`] is taken with `Computer::snapshot` and applied with `Computer::restore`, which
+//! makes it possible to branch several machines off the same state. Snapshots are written to
+//! text with [`Display`](fmt::Display) and read back with [`FromStr`]:
+//!
+//! ```text
+//! intcode-snapshot 1
+//! ip 4
+//! relative_base_offset 0
+//! halted false
+//! inputs 5
+//! outputs
+//! memory 0 3,11,3,12,1,11,12,13,4,13,99,4
+//! ```
+//!
+//! Memory is stored as runs of words, one `memory <address> <words>` line per run, and every word
+//! outside of a run is zero. This keeps programs that write to a few far apart addresses small.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::intcode::parse::{value, Lines, ParseError};
+use crate::intcode_computer::Word;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// The complete state of a computer, except for its input source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<W: Word = isize> {
+    /// Runs of words with the address of their first word, ordered by address.
+    pub memory: Vec<(usize, Vec<W>)>,
+    pub ip: usize,
+    pub relative_base_offset: usize,
+    pub halted: bool,
+    /// Queued inputs that haven't been consumed yet.
+    pub inputs: Vec<W>,
+    /// Outputs that haven't been taken yet.
+    pub outputs: Vec<W>,
+}
+
+fn write_list<W: Word>(f: &mut fmt::Formatter<'_>, name: &str, values: &[W]) -> fmt::Result {
+    let values: Vec<_> = values.iter().map(ToString::to_string).collect();
+
+    if values.is_empty() {
+        writeln!(f, "{}", name)
+    } else {
+        writeln!(f, "{} {}", name, values.join(","))
+    }
+}
+
+impl<W: Word> fmt::Display for Snapshot<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "ip {}", self.ip)?;
+        writeln!(f, "relative_base_offset {}", self.relative_base_offset)?;
+        writeln!(f, "halted {}", self.halted)?;
+        write_list(f, "inputs", &self.inputs)?;
+        write_list(f, "outputs", &self.outputs)?;
+
+        for (address, words) in &self.memory {
+            write_list(f, &format!("memory {}", address), words)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn list<W: Word>(line: usize, values: &str) -> Result<Vec<W>, ParseError> {
+    if values.is_empty() {
+        return Ok(vec![]);
+    }
+
+    values.split(',').map(|v| value(line, v.trim())).collect()
+}
+
+impl<W: Word> FromStr for Snapshot<W> {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = Lines::new(s);
+        lines.header(HEADER)?;
+
+        let (line, ip) = lines.field("ip")?;
+        let ip = value(line, ip)?;
+        let (line, relative_base_offset) = lines.field("relative_base_offset")?;
+        let relative_base_offset = value(line, relative_base_offset)?;
+        let (line, halted) = lines.field("halted")?;
+        let halted = value(line, halted)?;
+        let (line, inputs) = lines.field("inputs")?;
+        let inputs = list(line, inputs)?;
+        let (line, outputs) = lines.field("outputs")?;
+        let outputs = list(line, outputs)?;
+
+        let mut memory = vec![];
+        for (line, text) in lines.remaining() {
+            let mut parts = text.splitn(3, ' ');
+
+            if parts.next() != Some("memory") {
+                return Err(ParseError::new(line, "Expected `memory`"));
+            }
+
+            let address = value(line, parts.next().unwrap_or(""))?;
+            let words = list(line, parts.next().unwrap_or("").trim())?;
+            memory.push((address, words));
+        }
+
+        Ok(Self {
+            memory,
+            ip,
+            relative_base_offset,
+            halted,
+            inputs,
+            outputs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Snapshot;
+    use crate::intcode::parse::ParseError;
+    use crate::intcode_computer::{Computer, ExecutionState, IntcodeErrorReason};
+
+    #[test]
+    fn test_round_trip() {
+        // Reads two values and outputs their sum
+        let mut computer = Computer::new(vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99]);
+        computer.push_inputs(vec![4, 5]);
+        computer.step().unwrap();
+
+        let text = computer.snapshot().to_string();
+        assert_eq!(
+            text,
+            "intcode-snapshot 1\n\
+             ip 2\n\
+             relative_base_offset 0\n\
+             halted false\n\
+             inputs 5\n\
+             outputs\n\
+             memory 0 3,11,3,12,1,11,12,13,4,13,99,4\n"
+        );
+
+        let snapshot: Snapshot = text.parse().unwrap();
+        let mut resumed = Computer::new(vec![]);
+        resumed.restore(&snapshot).unwrap();
+
+        assert_eq!(resumed.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(resumed.all_outputs(), &[9]);
+    }
+
+    #[test]
+    fn test_restore_out_of_range() {
+        let text = format!(
+            "intcode-snapshot 1\nip 0\nrelative_base_offset 0\nhalted false\ninputs\noutputs\n\
+             memory {} 1,2\n",
+            usize::MAX
+        );
+        let snapshot: Snapshot = text.parse().unwrap();
+
+        assert_eq!(
+            Computer::new(vec![]).restore(&snapshot),
+            Err(IntcodeErrorReason::AddressOutOfRange(
+                usize::MAX as i128 + 1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "intcode-snapshot 2\n".parse::<Snapshot>(),
+            Err(ParseError {
+                line: 1,
+                message: "Unsupported version `2`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-snapshot 1\nip x\n".parse::<Snapshot>(),
+            Err(ParseError {
+                line: 2,
+                message: "Invalid value `x`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-snapshot 1\nip 0\n".parse::<Snapshot>(),
+            Err(ParseError {
+                line: 3,
+                message: "Expected `relative_base_offset`".to_string()
+            })
+        );
+        assert_eq!(
+            "intcode-snapshot 1\nip 0\nrelative_base_offset 0\nhalted false\ninputs\noutputs\n\
+             memory 0 1,2\nmemory 20 x\n"
+                .parse::<Snapshot>(),
+            Err(ParseError {
+                line: 8,
+                message: "Invalid value `x`".to_string()
+            })
+        );
+    }
+}
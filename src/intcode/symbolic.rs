@@ -0,0 +1,741 @@
+//! Symbolic execution of Intcode programs, for finding inputs that make a program produce a value.
+//!
+//! [`execute`] runs a program with some words or inputs left unknown. Everything computed from an
+//! unknown becomes an [`Expr`] tree of additions, multiplications and comparisons. As long as the
+//! control flow doesn't depend on an unknown, the value of interest ends up as one expression.
+//!
+//! [`Problem::solve`] turns that expression into a polynomial and solves it for the target value,
+//! exactly for any unknown it's linear in and by evaluating the polynomial for the others. Programs
+//! that branch on unknowns, or values that aren't polynomials, fall back to running the program
+//! for every combination of values. Day 2 is solved like this:
+//!
+//! ```text
+//! let problem = Problem {
+//!     program,
+//!     unknowns: vec![(Symbol::Memory(1), 0..=99), (Symbol::Memory(2), 0..=99)],
+//!     goal: Goal::Memory(0),
+//!     target: 19690720,
+//! };
+//! let noun_and_verb = problem.solve().unwrap().values;
+//! ```
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::intcode_computer::{
+    ComparisonOp, Computer, ExecutionState, Instruction, IntcodeError, IntcodeErrorReason,
+    JumpCondition, Memory, Op, Parameter,
+};
+
+/// Number of instructions a program may execute, symbolically or not, before it's given up on.
+pub const STEP_LIMIT: u64 = 1_000_000;
+
+/// A part of a program's state that's unknown.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Symbol {
+    /// The word at the address, when the program starts.
+    Memory(usize),
+    /// The input with the index, counting the inputs the program reads from 0.
+    Input(usize),
+}
+
+/// A value computed from unknowns.
+pub type Value = Rc<Expr>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(isize),
+    /// The unknown with the index.
+    Unknown(usize),
+    Add(Value, Value),
+    Multiply(Value, Value),
+    /// 1 if the comparison holds, 0 otherwise.
+    Compare(ComparisonOp, Value, Value),
+    /// The word at an address that depends on unknowns.
+    Load(Value),
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<isize> {
+        match *self {
+            Expr::Const(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Unknown(index) => write!(f, "x{}", index),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Multiply(a, b) => write!(f, "({} * {})", a, b),
+            Expr::Compare(ComparisonOp::LessThan, a, b) => write!(f, "({} < {})", a, b),
+            Expr::Compare(ComparisonOp::Equal, a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(address) => write!(f, "[{}]", address),
+        }
+    }
+}
+
+fn constant(value: isize) -> Value {
+    Rc::new(Expr::Const(value))
+}
+
+/// `a op b`, folded as far as constants allow, wrapping like the computer does.
+fn apply(op: Op, a: &Value, b: &Value) -> Value {
+    match (op, a.constant(), b.constant()) {
+        (Op::Add, Some(a), Some(b)) => constant(a.wrapping_add(b)),
+        (Op::Multiply, Some(a), Some(b)) => constant(a.wrapping_mul(b)),
+        (Op::Add, Some(0), _) | (Op::Multiply, Some(1), _) => Rc::clone(b),
+        (Op::Add, _, Some(0)) | (Op::Multiply, _, Some(1)) => Rc::clone(a),
+        (Op::Multiply, Some(0), _) | (Op::Multiply, _, Some(0)) => constant(0),
+        (Op::Add, _, _) => Rc::new(Expr::Add(Rc::clone(a), Rc::clone(b))),
+        (Op::Multiply, _, _) => Rc::new(Expr::Multiply(Rc::clone(a), Rc::clone(b))),
+    }
+}
+
+fn compare(op: ComparisonOp, a: &Value, b: &Value) -> Value {
+    match (op, a.constant(), b.constant()) {
+        (ComparisonOp::LessThan, Some(a), Some(b)) => constant((a < b) as isize),
+        (ComparisonOp::Equal, Some(a), Some(b)) => constant((a == b) as isize),
+        _ => Rc::new(Expr::Compare(op, Rc::clone(a), Rc::clone(b))),
+    }
+}
+
+/// Why a program couldn't be executed symbolically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unsupported {
+    /// The instruction at the address depends on an unknown.
+    SymbolicCode(usize),
+    /// The jump at the address depends on an unknown.
+    SymbolicBranch(usize),
+    /// The instruction at the address writes to an address, or adjusts the relative base by an
+    /// amount, that depends on an unknown.
+    SymbolicAddress(usize),
+    Fault(IntcodeError),
+    StepLimitReached,
+}
+
+/// The state of a program once it stopped, by halting or by needing more inputs than are unknown.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    memory: HashMap<usize, Value>,
+    pub outputs: Vec<Value>,
+    pub halted: bool,
+}
+
+impl Execution {
+    pub fn memory(&self, address: usize) -> Value {
+        self.memory
+            .get(&address)
+            .cloned()
+            .unwrap_or_else(|| constant(0))
+    }
+}
+
+/// Run `program` with the values of `unknowns` as [`Expr::Unknown`], indexed like `unknowns`.
+///
+/// The program may read as many inputs as there are [`Symbol::Input`] unknowns, which have to be
+/// numbered from 0, and stops when it needs another one.
+pub fn execute(program: &[isize], unknowns: &[Symbol]) -> Result<Execution, Unsupported> {
+    let mut memory: HashMap<_, _> = program
+        .iter()
+        .enumerate()
+        .map(|(address, &word)| (address, constant(word)))
+        .collect();
+    let mut inputs: BTreeMap<usize, Value> = BTreeMap::new();
+
+    for (index, &symbol) in unknowns.iter().enumerate() {
+        match symbol {
+            Symbol::Memory(address) => {
+                memory.insert(address, Rc::new(Expr::Unknown(index)));
+            }
+            Symbol::Input(input) => {
+                inputs.insert(input, Rc::new(Expr::Unknown(index)));
+            }
+        }
+    }
+    assert!(
+        inputs.keys().copied().eq(0..inputs.len()),
+        "Unknown inputs have to be numbered from 0"
+    );
+
+    let mut inputs = inputs.into_values();
+    let mut outputs = vec![];
+    let mut ip = 0;
+    let mut relative_base: isize = 0;
+
+    for _ in 0..STEP_LIMIT {
+        let words: Vec<_> = (ip..ip + 4)
+            .map(|address| memory.get(&address).cloned().unwrap_or_else(|| constant(0)))
+            .collect();
+        if words[0].constant().is_none() {
+            return Err(Unsupported::SymbolicCode(ip));
+        }
+
+        // Unknown parameters are decoded as 0 and resolved below
+        let concrete: Vec<_> = words
+            .iter()
+            .map(|word| word.constant().unwrap_or(0))
+            .collect();
+        let instruction = Instruction::parse(ip, &concrete).map_err(Unsupported::Fault)?;
+
+        let fault = |parameter, reason| {
+            Unsupported::Fault(IntcodeError {
+                ip,
+                opcode: concrete[0] as i128,
+                parameter: Some(parameter),
+                reason,
+            })
+        };
+        let address = |index: usize, parameter: Parameter| {
+            if words[index + 1].constant().is_none() {
+                return Err(Unsupported::SymbolicAddress(ip));
+            }
+
+            parameter
+                .address(relative_base as usize)
+                .map_err(|reason| fault(index, reason))
+        };
+        let value = |index: usize, parameter: Parameter| {
+            let word = &words[index + 1];
+
+            match (parameter, word.constant()) {
+                (Parameter::Immediate(_), _) => Ok(Rc::clone(word)),
+                (_, Some(_)) => {
+                    let address = parameter
+                        .address(relative_base as usize)
+                        .map_err(|reason| fault(index, reason))?;
+
+                    Ok(memory.get(&address).cloned().unwrap_or_else(|| constant(0)))
+                }
+                (Parameter::Position(_), None) => Ok(Rc::new(Expr::Load(Rc::clone(word)))),
+                (_, None) => Ok(Rc::new(Expr::Load(apply(
+                    Op::Add,
+                    word,
+                    &constant(relative_base),
+                )))),
+            }
+        };
+
+        let mut next = ip + instruction.length();
+        match instruction {
+            Instruction::Op(op, a1, a2, a3) => {
+                let result = apply(op, &value(0, a1)?, &value(1, a2)?);
+                memory.insert(address(2, a3)?, result);
+            }
+            Instruction::Compare(op, a1, a2, a3) => {
+                let result = compare(op, &value(0, a1)?, &value(1, a2)?);
+                memory.insert(address(2, a3)?, result);
+            }
+            Instruction::ConditionalJump(condition, a1, a2) => {
+                let (condition_value, target) = (value(0, a1)?, value(1, a2)?);
+                let should_jump = match (condition, condition_value.constant()) {
+                    (JumpCondition::IfTrue, Some(value)) => value != 0,
+                    (JumpCondition::IfFalse, Some(value)) => value == 0,
+                    (_, None) => return Err(Unsupported::SymbolicBranch(ip)),
+                };
+
+                if should_jump {
+                    match target.constant() {
+                        Some(target) if target >= 0 => next = target as usize,
+                        Some(target) => {
+                            return Err(fault(
+                                1,
+                                IntcodeErrorReason::NegativeAddress(target as i128),
+                            ))
+                        }
+                        None => return Err(Unsupported::SymbolicBranch(ip)),
+                    }
+                }
+            }
+            Instruction::Input(a1) => {
+                let address = address(0, a1)?;
+
+                match inputs.next() {
+                    Some(input) => {
+                        memory.insert(address, input);
+                    }
+                    None => {
+                        return Ok(Execution {
+                            memory,
+                            outputs,
+                            halted: false,
+                        })
+                    }
+                }
+            }
+            Instruction::Output(a1) => outputs.push(value(0, a1)?),
+            Instruction::RelativeBaseAdjust(a1) => match value(0, a1)?.constant() {
+                Some(adjustment) => {
+                    relative_base = relative_base.checked_add(adjustment).ok_or_else(|| {
+                        fault(
+                            0,
+                            IntcodeErrorReason::AddressOutOfRange(
+                                relative_base as i128 + adjustment as i128,
+                            ),
+                        )
+                    })?
+                }
+                None => return Err(Unsupported::SymbolicAddress(ip)),
+            },
+            Instruction::Halt => {
+                return Ok(Execution {
+                    memory,
+                    outputs,
+                    halted: true,
+                })
+            }
+            Instruction::Extended(_) => unreachable!("Extensions are never decoded"),
+        }
+
+        ip = next;
+    }
+
+    Err(Unsupported::StepLimitReached)
+}
+
+/// A polynomial over the unknowns, as exponents of every unknown mapped to a coefficient.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Polynomial {
+    terms: BTreeMap<Vec<u32>, i128>,
+}
+
+impl Polynomial {
+    fn constant(value: i128, unknowns: usize) -> Self {
+        let mut polynomial = Self::default();
+        polynomial.add_term(vec![0; unknowns], value);
+
+        polynomial
+    }
+
+    fn add_term(&mut self, exponents: Vec<u32>, coefficient: i128) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&exponents)
+            .unwrap_or(&0)
+            .checked_add(coefficient)?;
+
+        if sum == 0 {
+            self.terms.remove(&exponents);
+        } else {
+            self.terms.insert(exponents, sum);
+        }
+
+        Some(())
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        let mut sum = self.clone();
+        for (exponents, &coefficient) in &other.terms {
+            sum.add_term(exponents.clone(), coefficient)?;
+        }
+
+        Some(sum)
+    }
+
+    fn multiply(&self, other: &Self) -> Option<Self> {
+        let mut product = Self::default();
+        for (a, &x) in &self.terms {
+            for (b, &y) in &other.terms {
+                let exponents = a.iter().zip(b).map(|(a, b)| a + b).collect();
+                product.add_term(exponents, x.checked_mul(y)?)?;
+            }
+        }
+
+        Some(product)
+    }
+
+    /// The highest power of the unknown in any term.
+    fn degree(&self, unknown: usize) -> u32 {
+        self.terms
+            .keys()
+            .map(|exponents| exponents[unknown])
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn evaluate(&self, values: &[isize]) -> Option<i128> {
+        self.terms
+            .iter()
+            .try_fold(0_i128, |sum, (exponents, &coefficient)| {
+                let term = exponents.iter().zip(values).try_fold(
+                    coefficient,
+                    |term, (&exponent, &value)| {
+                        term.checked_mul((value as i128).checked_pow(exponent)?)
+                    },
+                )?;
+
+                sum.checked_add(term)
+            })
+    }
+
+    /// Split into the terms with `unknown` to the first power, without it, and the other terms.
+    fn split(&self, unknown: usize) -> (Self, Self) {
+        let (mut linear, mut rest) = (Self::default(), Self::default());
+
+        for (exponents, &coefficient) in &self.terms {
+            if exponents[unknown] == 1 {
+                let mut exponents = exponents.clone();
+                exponents[unknown] = 0;
+                linear.terms.insert(exponents, coefficient);
+            } else {
+                rest.terms.insert(exponents.clone(), coefficient);
+            }
+        }
+
+        (linear, rest)
+    }
+}
+
+/// `value` as a polynomial, or `None` if it has comparisons or loads, or its coefficients overflow.
+fn polynomial(
+    value: &Value,
+    unknowns: usize,
+    cache: &mut HashMap<*const Expr, Option<Polynomial>>,
+) -> Option<Polynomial> {
+    if let Some(cached) = cache.get(&Rc::as_ptr(value)) {
+        return cached.clone();
+    }
+
+    let result = match &**value {
+        Expr::Const(value) => Some(Polynomial::constant(*value as i128, unknowns)),
+        Expr::Unknown(index) => {
+            let mut exponents = vec![0; unknowns];
+            exponents[*index] = 1;
+
+            let mut polynomial = Polynomial::default();
+            polynomial.terms.insert(exponents, 1);
+            Some(polynomial)
+        }
+        Expr::Add(a, b) => polynomial(a, unknowns, cache)
+            .and_then(|a| polynomial(b, unknowns, cache).and_then(|b| a.add(&b))),
+        Expr::Multiply(a, b) => polynomial(a, unknowns, cache)
+            .and_then(|a| polynomial(b, unknowns, cache).and_then(|b| a.multiply(&b))),
+        Expr::Compare(_, _, _) | Expr::Load(_) => None,
+    };
+
+    cache.insert(Rc::as_ptr(value), result.clone());
+    result
+}
+
+/// Every combination of values from `domains`, the last one changing fastest.
+fn combinations(domains: Vec<RangeInclusive<isize>>) -> impl Iterator<Item = Vec<isize>> {
+    let mut next = if domains.iter().any(|domain| domain.is_empty()) {
+        None
+    } else {
+        Some(
+            domains
+                .iter()
+                .map(|domain| *domain.start())
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    std::iter::from_fn(move || {
+        let current = next.take()?;
+
+        let mut following = current.clone();
+        for index in (0..domains.len()).rev() {
+            if following[index] < *domains[index].end() {
+                following[index] += 1;
+                next = Some(following);
+                break;
+            }
+
+            following[index] = *domains[index].start();
+        }
+
+        Some(current)
+    })
+}
+
+/// Find values within `domains` for which `polynomial` is zero and that `accept` agrees with.
+fn find_root<F>(
+    polynomial: &Polynomial,
+    domains: &[RangeInclusive<isize>],
+    mut accept: F,
+) -> Option<Vec<isize>>
+where
+    F: FnMut(&[isize]) -> bool,
+{
+    let relevant: Vec<_> = (0..domains.len())
+        .filter(|&unknown| polynomial.degree(unknown) > 0)
+        .collect();
+    let solved = relevant
+        .iter()
+        .copied()
+        .find(|&unknown| polynomial.degree(unknown) == 1);
+
+    // Unknowns the polynomial doesn't depend on keep their first value
+    let searched = domains
+        .iter()
+        .enumerate()
+        .map(|(unknown, domain)| {
+            if relevant.contains(&unknown) && Some(unknown) != solved {
+                domain.clone()
+            } else {
+                *domain.start()..=*domain.start()
+            }
+        })
+        .collect();
+
+    let (linear, rest) = match solved {
+        Some(unknown) => polynomial.split(unknown),
+        None => (Polynomial::default(), polynomial.clone()),
+    };
+
+    combinations(searched).find_map(|mut values| {
+        let (a, b) = (linear.evaluate(&values)?, rest.evaluate(&values)?);
+
+        // a * x + b = 0, where x is the solved unknown if there is one
+        match solved {
+            Some(unknown) if a != 0 => {
+                if b % a != 0 {
+                    return None;
+                }
+
+                let x = isize::try_from(-b / a).ok()?;
+                if !domains[unknown].contains(&x) {
+                    return None;
+                }
+                values[unknown] = x;
+            }
+            _ if b != 0 => return None,
+            _ => (),
+        }
+
+        if accept(&values) {
+            Some(values)
+        } else {
+            None
+        }
+    })
+}
+
+/// Where the value a [`Problem`] is about ends up.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Goal {
+    /// The word at the address once the program stopped.
+    Memory(usize),
+    /// The output with the index, counting from 0.
+    Output(usize),
+}
+
+/// How a [`Solution`] was found.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Method {
+    /// By solving the expression for the goal.
+    Symbolic,
+    /// By running the program for every combination of values.
+    Enumeration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    /// Values of the unknowns, in the order of [`Problem::unknowns`].
+    pub values: Vec<isize>,
+    pub method: Method,
+}
+
+/// Values for unknown parts of a program, within the given ranges, that make `goal` equal to
+/// `target`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub program: Vec<isize>,
+    pub unknowns: Vec<(Symbol, RangeInclusive<isize>)>,
+    pub goal: Goal,
+    pub target: isize,
+}
+
+impl Problem {
+    /// Whether running the program with `values` for the unknowns reaches the target.
+    ///
+    /// The program has to stop by halting or needing more input within [`STEP_LIMIT`] steps.
+    pub fn is_solution(&self, values: &[isize]) -> bool {
+        let mut computer = Computer::new(self.program.clone());
+        let mut inputs = vec![];
+
+        for (&(symbol, _), &value) in self.unknowns.iter().zip(values) {
+            match symbol {
+                Symbol::Memory(address) => {
+                    if computer.memory_mut().write(address, value).is_err() {
+                        return false;
+                    }
+                }
+                Symbol::Input(index) => inputs.push((index, value)),
+            }
+        }
+        inputs.sort_unstable();
+        computer.push_inputs(inputs.into_iter().map(|(_, value)| value));
+
+        match computer.run_for(STEP_LIMIT, false) {
+            Ok(ExecutionState::Halted) | Ok(ExecutionState::NeedsInput) => (),
+            _ => return false,
+        }
+
+        let reached = match self.goal {
+            Goal::Memory(address) => Some(computer.memory().read(address)),
+            Goal::Output(index) => computer.all_outputs().get(index).copied(),
+        };
+
+        reached == Some(self.target)
+    }
+
+    /// The first solution found, symbolically if possible and by enumeration otherwise.
+    ///
+    /// Symbolic solutions are checked by running the program, so they also hold if it overflows.
+    pub fn solve(&self) -> Option<Solution> {
+        match self.solve_symbolically() {
+            Ok(values) => values.map(|values| Solution {
+                values,
+                method: Method::Symbolic,
+            }),
+            Err(_) => self.enumerate().map(|values| Solution {
+                values,
+                method: Method::Enumeration,
+            }),
+        }
+    }
+
+    /// The goal as an expression over the unknowns, if the program can be executed symbolically.
+    pub fn goal_expression(&self) -> Result<Option<Value>, Unsupported> {
+        let symbols: Vec<_> = self.unknowns.iter().map(|&(symbol, _)| symbol).collect();
+        let execution = execute(&self.program, &symbols)?;
+
+        Ok(match self.goal {
+            Goal::Memory(address) => Some(execution.memory(address)),
+            Goal::Output(index) => execution.outputs.get(index).cloned(),
+        })
+    }
+
+    /// Solve the goal's expression, `Err` if it isn't a polynomial over the unknowns.
+    fn solve_symbolically(&self) -> Result<Option<Vec<isize>>, ()> {
+        let expression = match self.goal_expression().map_err(|_| ())? {
+            Some(expression) => expression,
+            // The control flow doesn't depend on the unknowns, so the goal is never reached
+            None => return Ok(None),
+        };
+
+        let unknowns = self.unknowns.len();
+        let polynomial = polynomial(&expression, unknowns, &mut HashMap::new())
+            .and_then(|polynomial| {
+                polynomial.add(&Polynomial::constant(-(self.target as i128), unknowns))
+            })
+            .ok_or(())?;
+        let domains: Vec<_> = self
+            .unknowns
+            .iter()
+            .map(|(_, domain)| domain.clone())
+            .collect();
+
+        Ok(find_root(&polynomial, &domains, |values| {
+            self.is_solution(values)
+        }))
+    }
+
+    fn enumerate(&self) -> Option<Vec<isize>> {
+        let domains = self
+            .unknowns
+            .iter()
+            .map(|(_, domain)| domain.clone())
+            .collect();
+
+        combinations(domains).find(|values| self.is_solution(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{execute, Goal, Method, Problem, Symbol, Unsupported};
+    use crate::intcode_computer::{IntcodeError, IntcodeErrorReason};
+
+    #[test]
+    fn test_expressions() {
+        // Multiplies its two inputs, adds the word at 18 and outputs the result
+        let program = vec![
+            3, 15, 3, 16, 2, 15, 16, 17, 1, 17, 18, 17, 4, 17, 99, 0, 0, 0, 5,
+        ];
+        let execution = execute(&program, &[Symbol::Input(0), Symbol::Input(1)]).unwrap();
+
+        assert!(execution.halted);
+        assert_eq!(execution.outputs.len(), 1);
+        assert_eq!(execution.outputs[0].to_string(), "((x0 * x1) + 5)");
+
+        let execution = execute(&program, &[Symbol::Input(0), Symbol::Memory(18)]).unwrap();
+        assert!(!execution.halted);
+        assert!(execution.outputs.is_empty());
+        assert_eq!(execution.memory(15).to_string(), "x0");
+    }
+
+    #[test]
+    fn test_relative_base_overflow() {
+        for &(program, reason) in &[
+            (
+                [109, -isize::MAX, 209, -isize::MAX, 99],
+                IntcodeErrorReason::NegativeAddress(-2 * isize::MAX as i128),
+            ),
+            (
+                [109, isize::MAX, 109, 1, 99],
+                IntcodeErrorReason::AddressOutOfRange(isize::MAX as i128 + 1),
+            ),
+        ] {
+            assert_eq!(
+                execute(&program, &[]).unwrap_err(),
+                Unsupported::Fault(IntcodeError {
+                    ip: 2,
+                    opcode: program[2] as i128,
+                    parameter: Some(0),
+                    reason,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_linear_solution() {
+        // Outputs 3 * input + the word at 20 - 7
+        let program = vec![
+            3, 21, 1002, 21, 3, 21, 1, 21, 20, 21, 1001, 21, -7, 21, 4, 21, 99, 0, 0, 0, 0, 0,
+        ];
+        let problem = Problem {
+            program,
+            unknowns: vec![(Symbol::Input(0), -100..=100), (Symbol::Memory(20), 0..=99)],
+            goal: Goal::Output(0),
+            target: 200,
+        };
+
+        assert_eq!(
+            problem.goal_expression().unwrap().unwrap().to_string(),
+            "(((x0 * 3) + x1) + -7)"
+        );
+
+        let solution = problem.solve().unwrap();
+        assert_eq!(solution.method, Method::Symbolic);
+        assert_eq!(solution.values, vec![69, 0]);
+    }
+
+    #[test]
+    fn test_fallback_to_enumeration() {
+        // Outputs 1 if the input is less than 5, jumping on the comparison
+        let program = vec![1007, 9, 5, 10, 1005, 10, 11, 99, 0, 0, 0, 104, 1, 99];
+        let problem = Problem {
+            program,
+            unknowns: vec![(Symbol::Memory(9), 3..=9)],
+            goal: Goal::Output(0),
+            target: 1,
+        };
+
+        assert_eq!(
+            problem.goal_expression(),
+            Err(Unsupported::SymbolicBranch(4))
+        );
+
+        let solution = problem.solve().unwrap();
+        assert_eq!(solution.method, Method::Enumeration);
+        assert_eq!(solution.values, vec![3]);
+    }
+}
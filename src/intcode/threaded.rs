@@ -0,0 +1,301 @@
+//! Running Intcode machines on their own OS threads.
+//!
+//! A [`ThreadedComputer`] runs a single machine that's fed over an [`mpsc`] channel, blocking
+//! while it waits for input, and a [`Pool`] maps a function over many independent instances.
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::intcode_computer::{Computer, ExecutionState, IntcodeError, Memory};
+
+/// How often a machine blocked on input checks whether it was cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Number of instructions a running machine executes between checks for cancellation.
+const CANCEL_CHECK_STEPS: u64 = 4096;
+
+/// Why the thread of a [`ThreadedComputer`] stopped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Halted,
+    /// The program needed input after every sender of input was dropped.
+    InputClosed,
+    Cancelled,
+}
+
+/// A [`Computer`] running on its own thread, sending every output back as it's produced.
+///
+/// Dropping it cancels the machine and waits for the thread to stop.
+pub struct ThreadedComputer {
+    input: Option<Sender<isize>>,
+    outputs: Receiver<isize>,
+    cancelled: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<StopReason, IntcodeError>>>,
+}
+
+impl ThreadedComputer {
+    /// Start a thread running the computer returned by `build`.
+    ///
+    /// The computer is built on the new thread, so it doesn't need to be [`Send`]. Its input
+    /// source is replaced by the channel behind [`ThreadedComputer::send`], queued inputs are
+    /// still consumed first.
+    pub fn spawn<M, F>(build: F) -> Self
+    where
+        M: Memory<Word = isize>,
+        F: FnOnce() -> Computer<M> + Send + 'static,
+    {
+        let (input, input_receiver) = mpsc::channel();
+        let (output_sender, outputs) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let thread_cancelled = Arc::clone(&cancelled);
+
+        let handle = thread::spawn(move || {
+            let mut computer = build();
+            let input_cancelled = Arc::clone(&thread_cancelled);
+            computer.set_input(move || loop {
+                if input_cancelled.load(Ordering::Relaxed) {
+                    return None;
+                }
+
+                match input_receiver.recv_timeout(CANCEL_POLL_INTERVAL) {
+                    Ok(value) => return Some(value),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                }
+            });
+
+            run(&mut computer, &thread_cancelled, &output_sender)
+        });
+
+        Self {
+            input: Some(input),
+            outputs,
+            cancelled,
+            handle: Some(handle),
+        }
+    }
+
+    /// Send an input value, returns `false` if the machine has stopped.
+    pub fn send(&self, value: isize) -> bool {
+        self.input
+            .as_ref()
+            .is_some_and(|input| input.send(value).is_ok())
+    }
+
+    /// A sender for input values, e.g. to connect the outputs of another machine.
+    pub fn sender(&self) -> Option<Sender<isize>> {
+        self.input.clone()
+    }
+
+    /// Stop sending input, the machine stops the next time it needs input that isn't available
+    /// once every other [`ThreadedComputer::sender`] has been dropped too.
+    pub fn close_input(&mut self) {
+        self.input = None;
+    }
+
+    /// Wait for the next output, `None` once the machine has stopped and every output was taken.
+    pub fn recv(&self) -> Option<isize> {
+        self.outputs.recv().ok()
+    }
+
+    /// Take the next output if there is one, without waiting.
+    pub fn try_recv(&self) -> Option<isize> {
+        self.outputs.try_recv().ok()
+    }
+
+    /// Wait for outputs until the machine stops, oldest first.
+    pub fn outputs(&self) -> impl Iterator<Item = isize> + '_ {
+        self.outputs.iter()
+    }
+
+    /// Ask the machine to stop, it does so within a few thousand instructions or as soon as it
+    /// waits for input.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Wait for the machine to stop, re-raising any panic from its thread.
+    pub fn join(mut self) -> Result<StopReason, IntcodeError> {
+        self.wait()
+            .expect("The thread is only joined once, when the computer is consumed")
+    }
+
+    fn wait(&mut self) -> Option<Result<StopReason, IntcodeError>> {
+        let handle = self.handle.take()?;
+
+        match handle.join() {
+            Ok(result) => Some(result),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Drop for ThreadedComputer {
+    fn drop(&mut self) {
+        self.cancel();
+
+        if !thread::panicking() {
+            let _ = self.wait();
+        }
+    }
+}
+
+fn run<M: Memory<Word = isize>>(
+    computer: &mut Computer<M>,
+    cancelled: &AtomicBool,
+    outputs: &Sender<isize>,
+) -> Result<StopReason, IntcodeError> {
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(StopReason::Cancelled);
+        }
+
+        match computer.run_for(CANCEL_CHECK_STEPS, true)? {
+            ExecutionState::Output(_) => {
+                for value in computer.drain_outputs() {
+                    // Nobody listening for outputs isn't a reason to stop the program
+                    let _ = outputs.send(value);
+                }
+            }
+            ExecutionState::Halted => return Ok(StopReason::Halted),
+            ExecutionState::NeedsInput if cancelled.load(Ordering::Relaxed) => {
+                return Ok(StopReason::Cancelled)
+            }
+            ExecutionState::NeedsInput => return Ok(StopReason::InputClosed),
+            ExecutionState::StepLimitReached
+            | ExecutionState::DeadlineReached
+            | ExecutionState::Yielded => (),
+        }
+    }
+}
+
+/// A fixed number of worker threads for running many independent machines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pool {
+    threads: usize,
+}
+
+impl Pool {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+        }
+    }
+
+    /// A pool with one thread per core, as reported by [`thread::available_parallelism`].
+    pub fn with_available_parallelism() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Call `f` with every item, spread over the pool's threads, and return the results in the
+    /// order of the items.
+    ///
+    /// `f` typically builds and runs a [`Computer`] per item. A panic in `f` is re-raised once
+    /// every thread has stopped.
+    pub fn map<T, R, I, F>(&self, items: I, f: F) -> Vec<R>
+    where
+        I: IntoIterator<Item = T>,
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Sync,
+    {
+        let jobs = Mutex::new(
+            items
+                .into_iter()
+                .enumerate()
+                .collect::<Vec<_>>()
+                .into_iter(),
+        );
+        let (results, received) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                let results = results.clone();
+                let (jobs, f) = (&jobs, &f);
+
+                scope.spawn(move || loop {
+                    let job = jobs.lock().expect("Another worker panicked").next();
+
+                    match job {
+                        Some((index, item)) => {
+                            let _ = results.send((index, f(item)));
+                        }
+                        None => break,
+                    }
+                });
+            }
+        });
+        drop(results);
+
+        let mut results: Vec<_> = received.into_iter().collect();
+        results.sort_by_key(|&(index, _)| index);
+
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pool, StopReason, ThreadedComputer};
+    use crate::intcode_computer::Computer;
+
+    // Outputs double every input
+    const DOUBLE: [isize; 12] = [3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+
+    fn double() -> Computer {
+        Computer::new(DOUBLE.to_vec())
+    }
+
+    #[test]
+    fn test_blocking_input_and_outputs() {
+        let mut computer = ThreadedComputer::spawn(double);
+
+        assert!(computer.send(3));
+        assert_eq!(computer.recv(), Some(6));
+        assert!(computer.send(4));
+        assert!(computer.send(5));
+        computer.close_input();
+
+        assert_eq!(computer.outputs().collect::<Vec<_>>(), vec![8, 10]);
+        assert_eq!(computer.join(), Ok(StopReason::InputClosed));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        // Loops forever without input
+        let spinning = ThreadedComputer::spawn(|| Computer::new(vec![1105, 1, 0]));
+        spinning.cancel();
+        assert_eq!(spinning.join(), Ok(StopReason::Cancelled));
+
+        let waiting = ThreadedComputer::spawn(double);
+        assert_eq!(waiting.try_recv(), None);
+        waiting.cancel();
+        assert_eq!(waiting.join(), Ok(StopReason::Cancelled));
+
+        let halting = ThreadedComputer::spawn(|| Computer::new(vec![104, 7, 99]));
+        assert_eq!(halting.recv(), Some(7));
+        assert_eq!(halting.recv(), None);
+        assert_eq!(halting.join(), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn test_pool_map() {
+        let pool = Pool::new(3);
+        let doubled = pool.map(1..=10, |input| {
+            let mut computer = double();
+            computer.push_input(input);
+
+            computer.run_until_halt_or_paused(true);
+            computer.last_output()
+        });
+
+        assert_eq!(doubled, (1..=10).map(|v| Some(v * 2)).collect::<Vec<_>>());
+        assert_eq!(Pool::new(0).threads(), 1);
+    }
+}
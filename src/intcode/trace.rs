@@ -0,0 +1,249 @@
+//! [`Tracer`]s for finding out what an Intcode program spends its time on.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::intcode::disasm::Line;
+use crate::intcode_computer::{Instruction, TraceEvent, Tracer};
+
+/// Number of addresses listed by the [`Display`](fmt::Display) implementation of [`Profile`].
+const HOTTEST_ADDRESSES: usize = 20;
+
+/// Writes one line per executed instruction, in the disassembler's syntax, followed by the
+/// values read and the write performed:
+///
+/// ```text
+/// 0004: add [11], [12], [13] ; 4, 5 -> [13] = 9
+/// ```
+pub struct TraceWriter<W: Write> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, error: None }
+    }
+
+    /// Flush the trace and return the writer, or the first error encountered while tracing.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+
+    fn write_event(&mut self, event: &TraceEvent) -> io::Result<()> {
+        let line = Line::Instruction {
+            address: event.ip,
+            instruction: event.instruction,
+        };
+        let reads: Vec<_> = event
+            .reads
+            .iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect();
+
+        write!(self.out, "{}", line)?;
+        if !reads.is_empty() || event.written.is_some() {
+            write!(self.out, " ;")?;
+        }
+        if !reads.is_empty() {
+            write!(self.out, " {}", reads.join(", "))?;
+        }
+        if let Some((address, value)) = event.written {
+            write!(self.out, " -> [{}] = {}", address, value)?;
+        }
+
+        writeln!(self.out)
+    }
+}
+
+impl<W: Write + 'static> Tracer for TraceWriter<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        if self.error.is_none() {
+            self.error = self.write_event(event).err();
+        }
+    }
+}
+
+/// Execution counts per address and per instruction kind.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    total: u64,
+    counts: HashMap<usize, u64>,
+    /// The most recently executed instruction at each address.
+    instructions: HashMap<usize, Instruction>,
+    mix: BTreeMap<&'static str, u64>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of executed instructions.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// How many times the instruction at each address was executed, ordered by address.
+    pub fn histogram(&self) -> BTreeMap<usize, u64> {
+        self.counts
+            .iter()
+            .map(|(&address, &count)| (address, count))
+            .collect()
+    }
+
+    /// How many times each instruction was executed, keyed by mnemonic.
+    pub fn opcode_mix(&self) -> &BTreeMap<&'static str, u64> {
+        &self.mix
+    }
+
+    /// The `count` most executed addresses, most executed first.
+    pub fn hottest(&self, count: usize) -> Vec<(usize, u64)> {
+        let mut counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(&address, &count)| (address, count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(count);
+
+        counts
+    }
+
+    fn percentage(&self, count: u64) -> f64 {
+        count as f64 * 100.0 / self.total.max(1) as f64
+    }
+}
+
+impl Tracer for Profile {
+    fn trace(&mut self, event: &TraceEvent) {
+        self.total += 1;
+        *self.counts.entry(event.ip).or_insert(0) += 1;
+        self.instructions.insert(event.ip, event.instruction);
+        *self.mix.entry(event.instruction.mnemonic()).or_insert(0) += 1;
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} instructions executed", self.total)?;
+
+        writeln!(f, "\nOpcode mix")?;
+        let mut mix: Vec<_> = self.mix.iter().collect();
+        mix.sort_by(|a, b| b.1.cmp(a.1));
+        for (mnemonic, &count) in mix {
+            writeln!(
+                f,
+                "{:<4}{:>12}{:>7.2}%",
+                mnemonic,
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        writeln!(f, "\nHottest addresses")?;
+        for (address, count) in self.hottest(HOTTEST_ADDRESSES) {
+            let line = Line::Instruction {
+                address,
+                instruction: self.instructions[&address],
+            };
+
+            writeln!(
+                f,
+                "{:<40}{:>12}{:>7.2}%",
+                line.to_string(),
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profile, TraceWriter};
+    use crate::intcode_computer::{Computer, ExecutionState};
+
+    // Counts down from the input, outputting every value
+    const COUNTDOWN: [isize; 13] = [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+
+    #[test]
+    fn test_trace_writer() {
+        let mut computer = Computer::new(COUNTDOWN.to_vec());
+        computer.set_tracer(TraceWriter::new(vec![]));
+        computer.push_input(1);
+
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+
+        let trace = computer
+            .take_tracer::<TraceWriter<Vec<u8>>>()
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            "0000: in [12] ; -> [12] = 1\n\
+             0002: out [12] ; 1\n\
+             0004: add [12], #-1, [12] ; 1, -1 -> [12] = 0\n\
+             0008: jnz [12], #2 ; 0, 2\n\
+             0011: hlt\n"
+        );
+        assert!(computer.take_tracer::<Profile>().is_none());
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut computer = Computer::new(COUNTDOWN.to_vec());
+        computer.set_tracer(Profile::new());
+        computer.push_input(3);
+        computer.try_run(false).unwrap();
+
+        // The wrong tracer type leaves the tracer attached
+        assert!(computer.take_tracer::<TraceWriter<Vec<u8>>>().is_none());
+        let profile = computer.take_tracer::<Profile>().unwrap();
+
+        assert_eq!(profile.total(), 11);
+        assert_eq!(
+            profile.histogram().into_iter().collect::<Vec<_>>(),
+            vec![(0, 1), (2, 3), (4, 3), (8, 3), (11, 1)]
+        );
+        assert_eq!(
+            profile.opcode_mix().iter().collect::<Vec<_>>(),
+            vec![
+                (&"add", &3),
+                (&"hlt", &1),
+                (&"in", &1),
+                (&"jnz", &3),
+                (&"out", &3)
+            ]
+        );
+        assert_eq!(profile.hottest(2), vec![(2, 3), (4, 3)]);
+        assert_eq!(
+            profile.to_string(),
+            "11 instructions executed\n\
+             \n\
+             Opcode mix\n\
+             add            3  27.27%\n\
+             jnz            3  27.27%\n\
+             out            3  27.27%\n\
+             hlt            1   9.09%\n\
+             in             1   9.09%\n\
+             \n\
+             Hottest addresses\n\
+             0002: out [12]                                     3  27.27%\n\
+             0004: add [12], #-1, [12]                          3  27.27%\n\
+             0008: jnz [12], #2                                 3  27.27%\n\
+             0000: in [12]                                      1   9.09%\n\
+             0011: hlt                                          1   9.09%\n"
+        );
+    }
+}
@@ -1,134 +1,510 @@
-#[derive(Debug)]
-enum Parameter {
-    Immediate(isize),
+use std::any::Any;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::intcode::compile::Threaded;
+use crate::intcode::snapshot::Snapshot;
+
+#[cfg(test)]
+mod conformance;
+mod extension;
+mod memory;
+mod word;
+
+pub use extension::{Access, Effect, Extensions, Operands};
+pub use memory::{Memory, PagedMemory, PAGE_SIZE};
+pub use word::Word;
+
+/// The reason an Intcode program could not continue executing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IntcodeErrorReason {
+    /// The lowest two digits of the instruction word are not a known opcode.
+    InvalidOpcode,
+    /// A parameter mode other than position(0), immediate(1) or relative(2) was used.
+    InvalidMode(usize),
+    /// An immediate mode parameter was used as the target of a write.
+    ImmediateWrite,
+    /// A parameter resolved to an address below zero.
+    NegativeAddress(i128),
+    /// A parameter resolved to an address, or relative base adjustment, too large for `isize`.
+    AddressOutOfRange(i128),
+    /// An addition or multiplication overflowed the word while overflow is trapped, see
+    /// [`Computer::set_overflow`].
+    Overflow,
+    /// A write to the address needed more memory than the memory limit allows.
+    MemoryLimitExceeded(usize),
+}
+
+/// A fault raised while decoding or executing an Intcode instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IntcodeError {
+    /// Address of the faulting instruction.
+    pub ip: usize,
+    /// The raw instruction word at `ip`, widened to fit any [`Word`].
+    pub opcode: i128,
+    /// Zero based index of the faulting parameter, when the fault can be attributed to one.
+    pub parameter: Option<usize>,
+    pub reason: IntcodeErrorReason,
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            IntcodeErrorReason::InvalidOpcode => write!(f, "Invalid opcode `{}`", self.opcode)?,
+            IntcodeErrorReason::InvalidMode(mode) => write!(
+                f,
+                "Unsupported parameter mode `{}` in instruction `{}`",
+                mode, self.opcode
+            )?,
+            IntcodeErrorReason::ImmediateWrite => write!(
+                f,
+                "Attempted to use an immediate mode parameter for addressing in instruction `{}`",
+                self.opcode
+            )?,
+            IntcodeErrorReason::NegativeAddress(address) => write!(
+                f,
+                "Attempted to access negative address `{}` in instruction `{}`",
+                address, self.opcode
+            )?,
+            IntcodeErrorReason::AddressOutOfRange(address) => write!(
+                f,
+                "Attempted to access out of range address `{}` in instruction `{}`",
+                address, self.opcode
+            )?,
+            IntcodeErrorReason::MemoryLimitExceeded(address) => write!(
+                f,
+                "Writing address `{}` exceeds the memory limit in instruction `{}`",
+                address, self.opcode
+            )?,
+            IntcodeErrorReason::Overflow => {
+                write!(f, "Arithmetic overflow in instruction `{}`", self.opcode)?
+            }
+        }
+
+        match self.parameter {
+            Some(parameter) => write!(f, " (parameter {}) at address {}", parameter, self.ip),
+            None => write!(f, " at address {}", self.ip),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
+
+/// A decoded instruction parameter.
+///
+/// Displays as `#value` in immediate mode, `[address]` in position mode and `[rb+offset]` in
+/// relative mode.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Parameter<W: Word = isize> {
+    Immediate(W),
     Position(usize),
     Relative(isize),
 }
 
-impl Parameter {
-    fn new(mode: usize, value: isize) -> Self {
+/// `word` as an address, or relative base adjustment when `negative` values are allowed.
+pub(crate) fn to_address<W: Word>(word: W, negative: bool) -> Result<isize, IntcodeErrorReason> {
+    match word.to_isize() {
+        Some(value) if value >= 0 || negative => Ok(value),
+        Some(value) => Err(IntcodeErrorReason::NegativeAddress(value as i128)),
+        None if word < W::ZERO && !negative => {
+            Err(IntcodeErrorReason::NegativeAddress(word.to_i128()))
+        }
+        None => Err(IntcodeErrorReason::AddressOutOfRange(word.to_i128())),
+    }
+}
+
+/// The relative base after adding `adjustment`, which may leave it below zero but not overflow.
+pub(crate) fn adjusted_relative_base(
+    relative_base_offset: usize,
+    adjustment: isize,
+) -> Result<usize, IntcodeErrorReason> {
+    (relative_base_offset as isize)
+        .checked_add(adjustment)
+        .map(|offset| offset as usize)
+        .ok_or(IntcodeErrorReason::AddressOutOfRange(
+            relative_base_offset as isize as i128 + adjustment as i128,
+        ))
+}
+
+impl<W: Word> Parameter<W> {
+    pub(crate) fn new(mode: usize, value: W) -> Result<Self, IntcodeErrorReason> {
         match mode {
-            0 => Self::Position(value as usize),
-            1 => Self::Immediate(value),
-            2 => Self::Relative(value),
-            _ => panic!("Unsupported paramter mode: `{}`", mode),
+            0 => Ok(Self::Position(to_address(value, false)? as usize)),
+            1 => Ok(Self::Immediate(value)),
+            2 => Ok(Self::Relative(to_address(value, true)?)),
+            _ => Err(IntcodeErrorReason::InvalidMode(mode)),
         }
     }
 
-    fn address(&self, relative_base_offset: usize) -> usize {
-        match self {
-            &Self::Position(position) => position,
-            &Self::Relative(position) => ((relative_base_offset as isize) + position) as usize,
-            &Self::Immediate(_) => {
-                panic!("Attempted to use an immediate mode paramter for addressing")
+    pub fn address(&self, relative_base_offset: usize) -> Result<usize, IntcodeErrorReason> {
+        match *self {
+            Self::Position(position) => Ok(position),
+            Self::Relative(position) => {
+                match (relative_base_offset as isize).checked_add(position) {
+                    Some(address) if address >= 0 => Ok(address as usize),
+                    Some(address) => Err(IntcodeErrorReason::NegativeAddress(address as i128)),
+                    None => {
+                        let address = relative_base_offset as isize as i128 + position as i128;
+
+                        if address < 0 {
+                            Err(IntcodeErrorReason::NegativeAddress(address))
+                        } else {
+                            Err(IntcodeErrorReason::AddressOutOfRange(address))
+                        }
+                    }
+                }
             }
+            Self::Immediate(_) => Err(IntcodeErrorReason::ImmediateWrite),
         }
     }
 }
 
-#[derive(Debug)]
-enum Op {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
     Add,
     Multiply,
 }
 
-#[derive(Debug)]
-enum JumpCondition {
+impl Op {
+    /// The result of the operation, or `None` if it overflows while overflow is trapped.
+    #[inline(always)]
+    pub fn apply<W: Word>(self, a: W, b: W, overflow: Overflow) -> Option<W> {
+        match (self, overflow) {
+            (Self::Add, Overflow::Wrap) => Some(a.wrapping_add(b)),
+            (Self::Multiply, Overflow::Wrap) => Some(a.wrapping_mul(b)),
+            (Self::Add, Overflow::Trap) => a.checked_add(b),
+            (Self::Multiply, Overflow::Trap) => a.checked_mul(b),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JumpCondition {
     IfTrue,
     IfFalse,
 }
 
-#[derive(Debug)]
-enum ComparisonOp {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ComparisonOp {
     LessThan,
     Equal,
 }
 
-#[derive(Debug)]
-enum Instruction {
-    Op(Op, Parameter, Parameter, Parameter), // Used for Add and Multiply
-    Input(Parameter),                        // 3
-    Output(Parameter),                       // 4
-    ConditionalJump(JumpCondition, Parameter, Parameter), // Used for JumpIfTrue and JumpIfFalse
-    Compare(ComparisonOp, Parameter, Parameter, Parameter), // Used for LessThan and Equals
-    RelativeBaseAdjust(Parameter),           // 9
-    Halt,                                    // 99
+/// A decoded Intcode instruction.
+///
+/// Displays as a mnemonic followed by its operands, e.g. `add #1, [9], [rb-2]`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Instruction<W: Word = isize> {
+    Op(Op, Parameter<W>, Parameter<W>, Parameter<W>), // Used for Add and Multiply
+    Input(Parameter<W>),                              // 3
+    Output(Parameter<W>),                             // 4
+    ConditionalJump(JumpCondition, Parameter<W>, Parameter<W>), // Used for JumpIfTrue and JumpIfFalse
+    Compare(ComparisonOp, Parameter<W>, Parameter<W>, Parameter<W>), // Used for LessThan and Equals
+    RelativeBaseAdjust(Parameter<W>),                           // 9
+    Halt,                                                       // 99
+    Extended(Extended<W>),                                      // Registered with `Extensions`
 }
 
-impl Instruction {
+/// An instruction with an opcode registered with [`Extensions`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Extended<W: Word = isize> {
+    pub opcode: usize,
+    pub mnemonic: &'static str,
+    parameters: [Parameter<W>; 3],
+    access: [Access; 3],
+    count: usize,
+}
+
+impl<W: Word> Extended<W> {
+    pub fn parameters(&self) -> &[Parameter<W>] {
+        &self.parameters[..self.count]
+    }
+
+    /// How each parameter is used, in the same order as [`Extended::parameters`].
+    pub fn access(&self) -> &[Access] {
+        &self.access[..self.count]
+    }
+}
+
+impl<W: Word> Instruction<W> {
+    /// The number of words this instruction occupies in memory.
     #[inline(always)]
-    fn length(&self) -> usize {
+    pub fn length(&self) -> usize {
         match self {
             Self::Op(_, _, _, _) | Self::Compare(_, _, _, _) => 4,
             Self::ConditionalJump(_, _, _) => 3,
             Self::Input(_) | Self::Output(_) | Self::RelativeBaseAdjust(_) => 2,
             Self::Halt => 1,
+            Self::Extended(extended) => 1 + extended.count,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Op(Op::Add, _, _, _) => "add",
+            Self::Op(Op::Multiply, _, _, _) => "mul",
+            Self::Input(_) => "in",
+            Self::Output(_) => "out",
+            Self::ConditionalJump(JumpCondition::IfTrue, _, _) => "jnz",
+            Self::ConditionalJump(JumpCondition::IfFalse, _, _) => "jz",
+            Self::Compare(ComparisonOp::LessThan, _, _, _) => "lt",
+            Self::Compare(ComparisonOp::Equal, _, _, _) => "eq",
+            Self::RelativeBaseAdjust(_) => "arb",
+            Self::Halt => "hlt",
+            Self::Extended(extended) => extended.mnemonic,
+        }
+    }
+
+    /// The parameter this instruction writes its result to, if any.
+    pub fn write_parameter(&self) -> Option<Parameter<W>> {
+        match *self {
+            Self::Op(_, _, _, a3) | Self::Compare(_, _, _, a3) => Some(a3),
+            Self::Input(a1) => Some(a1),
+            Self::Extended(extended) => extended
+                .access()
+                .iter()
+                .position(|&access| access == Access::Write)
+                .map(|index| extended.parameters[index]),
+            _ => None,
+        }
+    }
+
+    pub fn parameters(&self) -> Vec<Parameter<W>> {
+        match *self {
+            Self::Op(_, a1, a2, a3) | Self::Compare(_, a1, a2, a3) => vec![a1, a2, a3],
+            Self::ConditionalJump(_, a1, a2) => vec![a1, a2],
+            Self::Input(a1) | Self::Output(a1) | Self::RelativeBaseAdjust(a1) => vec![a1],
+            Self::Halt => vec![],
+            Self::Extended(extended) => extended.parameters().to_vec(),
         }
     }
 
-    fn parse(input: &[isize]) -> Self {
-        let opcode = input[0];
+    /// Decode the instruction at the start of `input`, which must hold at least four words.
+    ///
+    /// `ip` is the address of `input[0]` and is only used to report faults.
+    pub fn parse(ip: usize, input: &[W]) -> Result<Self, IntcodeError> {
+        Self::decode(ip, input, |_| None)
+    }
+
+    /// Decode the instruction at the start of `input` like [`Instruction::parse`], additionally
+    /// accepting the opcodes registered with `extensions`.
+    pub fn parse_extended<M>(
+        ip: usize,
+        input: &[W],
+        extensions: &Extensions<M>,
+    ) -> Result<Self, IntcodeError>
+    where
+        M: Memory<Word = W>,
+    {
+        Self::decode(ip, input, |opcode| extensions.signature(opcode))
+    }
+
+    fn decode<'a, E>(ip: usize, input: &[W], extension: E) -> Result<Self, IntcodeError>
+    where
+        E: Fn(usize) -> Option<(&'static str, &'a [Access])>,
+    {
+        let fault = |parameter, reason| IntcodeError {
+            ip,
+            opcode: input[0].to_i128(),
+            parameter,
+            reason,
+        };
+        // No valid instruction word needs more than a few digits
+        let opcode = input[0]
+            .to_isize()
+            .ok_or_else(|| fault(None, IntcodeErrorReason::InvalidOpcode))?;
 
         let code = opcode % 100;
-        let hundreds_digit = ((opcode / 100) % 10) as usize;
-        let thousands_digit = ((opcode / 1000) % 10) as usize;
-        let ten_thousands_digit = ((opcode / 10000) % 10) as usize;
-
-        match code {
-            1 => Self::Op(
-                Op::Add,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-                Parameter::new(ten_thousands_digit, input[3]),
-            ),
-            2 => Self::Op(
-                Op::Multiply,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-                Parameter::new(ten_thousands_digit, input[3]),
-            ),
-            3 => {
-                assert!(
-                    hundreds_digit != 1,
-                    "Immediate mode is not compatible with the Input opcode"
-                );
-
-                Self::Input(Parameter::new(hundreds_digit, input[1]))
+        let modes = [
+            ((opcode / 100) % 10) as usize,
+            ((opcode / 1000) % 10) as usize,
+            ((opcode / 10000) % 10) as usize,
+        ];
+
+        let read = |index: usize| {
+            Parameter::new(modes[index], input[index + 1]).map_err(|r| fault(Some(index), r))
+        };
+        let write = |index: usize| {
+            if modes[index] == 1 {
+                Err(fault(Some(index), IntcodeErrorReason::ImmediateWrite))
+            } else {
+                read(index)
             }
-            4 => Self::Output(Parameter::new(hundreds_digit, input[1])),
-            5 => Self::ConditionalJump(
-                JumpCondition::IfTrue,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-            ),
-            6 => Self::ConditionalJump(
-                JumpCondition::IfFalse,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-            ),
-            7 => Self::Compare(
-                ComparisonOp::LessThan,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-                Parameter::new(ten_thousands_digit, input[3]),
-            ),
-            8 => Self::Compare(
-                ComparisonOp::Equal,
-                Parameter::new(hundreds_digit, input[1]),
-                Parameter::new(thousands_digit, input[2]),
-                Parameter::new(ten_thousands_digit, input[3]),
-            ),
-            9 => Self::RelativeBaseAdjust(Parameter::new(hundreds_digit, input[1])),
+        };
+
+        let instruction = match code {
+            1 => Self::Op(Op::Add, read(0)?, read(1)?, write(2)?),
+            2 => Self::Op(Op::Multiply, read(0)?, read(1)?, write(2)?),
+            3 => Self::Input(write(0)?),
+            4 => Self::Output(read(0)?),
+            5 => Self::ConditionalJump(JumpCondition::IfTrue, read(0)?, read(1)?),
+            6 => Self::ConditionalJump(JumpCondition::IfFalse, read(0)?, read(1)?),
+            7 => Self::Compare(ComparisonOp::LessThan, read(0)?, read(1)?, write(2)?),
+            8 => Self::Compare(ComparisonOp::Equal, read(0)?, read(1)?, write(2)?),
+            9 => Self::RelativeBaseAdjust(read(0)?),
             99 => Self::Halt,
-            _ => panic!("Invalid opcode `{}`", opcode),
+            // Only opcodes outside the standard set consult the registry
+            _ => match usize::try_from(code).ok().and_then(extension) {
+                Some((mnemonic, access)) => {
+                    let parameter = |index: usize, kind: Access| match kind {
+                        Access::Read => read(index),
+                        Access::Write => write(index),
+                    };
+
+                    Self::decode_extended(code as usize, mnemonic, access, &parameter)?
+                }
+                None => return Err(fault(None, IntcodeErrorReason::InvalidOpcode)),
+            },
+        };
+
+        Ok(instruction)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn decode_extended(
+        opcode: usize,
+        mnemonic: &'static str,
+        access: &[Access],
+        parameter: &dyn Fn(usize, Access) -> Result<Parameter<W>, IntcodeError>,
+    ) -> Result<Self, IntcodeError> {
+        let mut extended = Extended {
+            opcode,
+            mnemonic,
+            parameters: [Parameter::Immediate(W::ZERO); 3],
+            access: [Access::Read; 3],
+            count: access.len(),
+        };
+
+        for (index, &kind) in access.iter().enumerate() {
+            extended.parameters[index] = parameter(index, kind)?;
+            extended.access[index] = kind;
         }
+
+        Ok(Self::Extended(extended))
     }
 }
 
-enum ExecutionState {
-    Halt,
-    Pause,
-    Continue,
+impl<W: Word> fmt::Display for Parameter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Immediate(value) => write!(f, "#{}", value),
+            Self::Position(address) => write!(f, "[{}]", address),
+            Self::Relative(offset) if offset < 0 => write!(f, "[rb{}]", offset),
+            Self::Relative(offset) => write!(f, "[rb+{}]", offset),
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<_> = self.parameters().iter().map(ToString::to_string).collect();
+
+        if parameters.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), parameters.join(", "))
+        }
+    }
+}
+
+/// Why a run of the computer stopped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExecutionState<W: Word = isize> {
+    /// The program executed the halt instruction.
+    Halted,
+    /// The program is waiting on input that isn't available yet.
+    NeedsInput,
+    /// The program produced an output and the run was asked to stop on outputs.
+    Output(W),
+    /// The run used up its step budget before anything else happened, see [`Computer::run_for`].
+    StepLimitReached,
+    /// The run was still going when the deadline passed, see [`Computer::set_deadline`].
+    DeadlineReached,
+    /// An extension instruction yielded, see [`Effect::Yield`].
+    Yielded,
+}
+
+/// How a [`Computer`] executes its program, see [`Computer::set_backend`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Decode and execute one instruction at a time.
+    Interpreter,
+    /// Compile basic blocks to closures the first time they're reached, see
+    /// [`intcode::compile`](crate::intcode::compile).
+    Compiled,
+}
+
+/// The interpreter, or the compiler when the `compiled` feature is enabled.
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "compiled") {
+            Self::Compiled
+        } else {
+            Self::Interpreter
+        }
+    }
+}
+
+/// What additions and multiplications do when the result doesn't fit in a [`Word`], see
+/// [`Computer::set_overflow`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Wrap around in two's complement, like release builds always did.
+    #[default]
+    Wrap,
+    /// Fault with [`IntcodeErrorReason::Overflow`].
+    Trap,
+}
+
+/// An executed instruction, as reported to a [`Tracer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TraceEvent<W: Word = isize> {
+    pub ip: usize,
+    pub instruction: Instruction<W>,
+    /// Values of the parameters that were read, indexed by parameter.
+    pub reads: [Option<W>; 3],
+    /// The address and value written by the instruction, if it writes.
+    pub written: Option<(usize, W)>,
+}
+
+/// Receives every instruction executed by a [`Computer`] it's attached to.
+///
+/// See [`Computer::set_tracer`].
+pub trait Tracer<W: Word = isize>: Any {
+    fn trace(&mut self, event: &TraceEvent<W>);
+}
+
+/// Report every event to both tracers.
+impl<W, A, B> Tracer<W> for (A, B)
+where
+    W: Word,
+    A: Tracer<W>,
+    B: Tracer<W>,
+{
+    fn trace(&mut self, event: &TraceEvent<W>) {
+        self.0.trace(event);
+        self.1.trace(event);
+    }
+}
+
+/// A source of input values, consulted by a [`Computer`] when its input queue is empty.
+pub trait InputSource<W: Word = isize> {
+    fn next_input(&mut self) -> Option<W>;
+}
+
+impl<W, F> InputSource<W> for F
+where
+    W: Word,
+    F: FnMut() -> Option<W>,
+{
+    fn next_input(&mut self) -> Option<W> {
+        self()
+    }
 }
 
 pub fn input_with_initial_value<F, T>(
@@ -154,205 +530,661 @@ where
     closure
 }
 
-pub struct GrowableMemory {
-    storage: Vec<isize>,
+/// Number of instructions executed between checks of the deadline.
+const DEADLINE_CHECK_STEPS: u64 = 4096;
+
+/// Instructions are only cached below this address, which keeps the cache small for programs
+/// that jump far into sparse memory.
+const INSTRUCTION_CACHE_LIMIT: usize = 1 << 16;
+
+pub struct Computer<M: Memory = PagedMemory> {
+    input_queue: VecDeque<M::Word>,
+    input_source: Option<Box<dyn InputSource<M::Word>>>,
+    tracer: Option<Box<dyn Tracer<M::Word>>>,
+    pub(crate) outputs: Vec<M::Word>,
+    pub(crate) memory: M,
+    /// Decoded instructions keyed by address, grown lazily once the first instruction is cached.
+    decoded: Vec<Option<Instruction<M::Word>>>,
+    cache_instructions: bool,
+    backend: Backend,
+    pub(crate) overflow: Overflow,
+    extensions: Option<Arc<Extensions<M>>>,
+    /// Blocks compiled by [`Backend::Compiled`], dropped whenever memory is replaced.
+    threaded: Option<Box<Threaded<M>>>,
+    /// Words that compiled blocks were built from, keyed by address.
+    pub(crate) code_words: Vec<bool>,
+    /// The most recently written word of `code_words`, which invalidates the compiled blocks.
+    pub(crate) modified_code: Option<usize>,
+    /// Number of instructions executed so far.
+    pub(crate) steps: u64,
+    deadline: Option<Instant>,
+    pub(crate) did_halt: bool,
+    pub(crate) ip: usize,
+    pub(crate) relative_base_offset: usize,
 }
 
-impl GrowableMemory {
-    fn new(program: Vec<isize>, initial_size: usize) -> Self {
-        let mut storage: Vec<isize> = program.clone();
-        storage.resize_with(initial_size, Default::default);
+impl Computer {
+    pub fn new(program: Vec<isize>) -> Self {
+        Self::with_memory(PagedMemory::new(&program))
+    }
+
+    pub fn with_input<S>(program: Vec<isize>, input: S) -> Self
+    where
+        S: InputSource + 'static,
+    {
+        let mut computer = Self::new(program);
+        computer.set_input(input);
 
-        Self { storage }
+        computer
     }
+}
 
-    fn len(&self) -> usize {
-        self.storage.len()
+impl<M: Memory> Computer<M> {
+    /// Create a computer that runs the program already loaded into `memory`.
+    pub fn with_memory(memory: M) -> Self {
+        Self {
+            input_queue: VecDeque::new(),
+            input_source: None,
+            tracer: None,
+            outputs: vec![],
+            memory,
+            decoded: vec![],
+            cache_instructions: true,
+            backend: Backend::default(),
+            overflow: Overflow::default(),
+            extensions: None,
+            threaded: None,
+            code_words: vec![],
+            modified_code: None,
+            steps: 0,
+            deadline: None,
+            did_halt: false,
+            ip: 0,
+            relative_base_offset: 0,
+        }
     }
 
-    #[inline(always)]
-    pub fn read(&mut self, address: usize) -> isize {
-        if address < self.storage.len() {
-            self.storage[address]
-        } else {
-            self.storage
-                .resize_with(self.storage.len() * 10, Default::default);
+    pub fn is_halted(&self) -> bool {
+        self.did_halt
+    }
+
+    /// Set the source consulted for input once the input queue is empty.
+    pub fn set_input<S>(&mut self, input: S)
+    where
+        S: InputSource<M::Word> + 'static,
+    {
+        self.input_source = Some(Box::new(input));
+    }
+
+    /// Queue a value to be consumed by the next input instruction.
+    pub fn push_input(&mut self, value: M::Word) {
+        self.input_queue.push_back(value);
+    }
+
+    /// Queue several values to be consumed, in order, by upcoming input instructions.
+    pub fn push_inputs<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = M::Word>,
+    {
+        self.input_queue.extend(values);
+    }
+
+    /// Number of queued inputs that haven't been consumed yet.
+    pub fn pending_inputs(&self) -> usize {
+        self.input_queue.len()
+    }
 
-            self.storage[address]
+    pub fn run_until_halt_or_paused(&mut self, stop_on_output: bool) -> ExecutionState<M::Word> {
+        match self.try_run(stop_on_output) {
+            Ok(state) => state,
+            Err(error) => panic!("{}", error),
         }
     }
 
-    fn read_instruction(&mut self, address: usize) -> &[isize] {
-        if address < self.storage.len() {
-            &self.storage[address..address + 4]
+    /// Run the program until it halts, pauses or faults.
+    ///
+    /// See [`Computer::run_until_halt_or_paused`] for when the program pauses.
+    pub fn try_run(
+        &mut self,
+        stop_on_output: bool,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        self.run_budgeted(stop_on_output, None)
+    }
+
+    /// Like [`Computer::try_run`], but execute at most `max_steps` instructions.
+    ///
+    /// Returns [`ExecutionState::StepLimitReached`] when the budget runs out before the program
+    /// stops on its own, it can be resumed with another run.
+    pub fn run_for(
+        &mut self,
+        max_steps: u64,
+        stop_on_output: bool,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        self.run_budgeted(stop_on_output, Some(max_steps))
+    }
+
+    /// Stop every run that's still going at `deadline` with [`ExecutionState::DeadlineReached`],
+    /// or never with `None`, which is the default.
+    ///
+    /// The clock is only checked every few thousand instructions, so a run can overshoot the
+    /// deadline slightly. Single steps ignore the deadline.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Total number of instructions executed by this computer.
+    ///
+    /// Input instructions that had to wait for input are only counted once they're executed.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    fn run_budgeted(
+        &mut self,
+        stop_on_output: bool,
+        max_steps: Option<u64>,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        let limit = max_steps.map_or(u64::MAX, |max_steps| self.steps.saturating_add(max_steps));
+
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return self.run_until(stop_on_output, limit),
+        };
+
+        loop {
+            if Instant::now() >= deadline {
+                return Ok(ExecutionState::DeadlineReached);
+            }
+
+            let chunk = limit.min(self.steps.saturating_add(DEADLINE_CHECK_STEPS));
+            match self.run_until(stop_on_output, chunk)? {
+                ExecutionState::StepLimitReached if self.steps < limit => (),
+                state => return Ok(state),
+            }
+        }
+    }
+
+    /// Run until the program stops on its own or `limit` instructions were executed in total.
+    fn run_until(
+        &mut self,
+        stop_on_output: bool,
+        limit: u64,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        // Compiled blocks don't report individual instructions, so tracing always interprets
+        if self.backend == Backend::Compiled && self.tracer.is_none() {
+            let mut threaded = self.threaded.take().unwrap_or_default();
+            let result = threaded.run(self, stop_on_output, limit);
+            self.threaded = Some(threaded);
+
+            return result;
+        }
+
+        // Separate loops keep the untraced one free of any tracing checks, and runs of programs
+        // that were never compiled free of checking for writes to compiled code
+        if self.tracer.is_some() {
+            self.interpret::<true, true>(stop_on_output, limit)
+        } else if self.code_words.is_empty() {
+            self.interpret::<false, false>(stop_on_output, limit)
         } else {
-            self.storage
-                .resize_with(self.storage.len() * 2, Default::default);
+            self.interpret::<false, true>(stop_on_output, limit)
+        }
+    }
 
-            &self.storage[address..address + 4]
+    fn interpret<const TRACE: bool, const COMPILED: bool>(
+        &mut self,
+        stop_on_output: bool,
+        limit: u64,
+    ) -> Result<ExecutionState<M::Word>, IntcodeError> {
+        while self.steps < limit {
+            if let Some(state) = self.execute_next_instruction::<TRACE, COMPILED>(stop_on_output)? {
+                return Ok(state);
+            }
         }
+
+        Ok(ExecutionState::StepLimitReached)
     }
 
-    #[inline(always)]
-    pub fn write(&mut self, address: usize, value: isize) {
-        if address < self.storage.len() {
-            self.storage[address] = value;
+    /// Execute exactly one instruction.
+    ///
+    /// Returns `None` when the program can keep going, otherwise the state the program stopped
+    /// in. Outputs always stop the program. An input instruction without available input isn't
+    /// executed, it's retried by the next step.
+    pub fn step(&mut self) -> Result<Option<ExecutionState<M::Word>>, IntcodeError> {
+        if self.tracer.is_some() {
+            self.execute_next_instruction::<true, true>(true)
+        } else if self.code_words.is_empty() {
+            self.execute_next_instruction::<false, false>(true)
         } else {
-            self.storage
-                .resize_with(self.storage.len() * 10, Default::default);
-            self.storage[address] = value;
+            self.execute_next_instruction::<false, true>(true)
+        }
+    }
+
+    /// Choose how [`Computer::try_run`] executes the program, the default is [`Backend::default`].
+    ///
+    /// Single steps, and runs with a tracer attached, are always interpreted.
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Choose whether additions and multiplications that overflow a word wrap around or fault,
+    /// the default is [`Overflow::Wrap`].
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        self.overflow = overflow;
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Decode and execute the opcodes registered with `extensions` in addition to the standard
+    /// ones, or only the standard opcodes with `None`, which is the default.
+    ///
+    /// Extension instructions are always interpreted, even with [`Backend::Compiled`].
+    pub fn set_extensions(&mut self, extensions: Option<Arc<Extensions<M>>>) {
+        self.extensions = extensions;
+        self.discard_compiled();
+    }
+
+    pub fn extensions(&self) -> Option<&Extensions<M>> {
+        self.extensions.as_deref()
+    }
+
+    /// Report every executed instruction to `tracer`, replacing any previous tracer.
+    pub fn set_tracer<T>(&mut self, tracer: T)
+    where
+        T: Tracer<M::Word> + 'static,
+    {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// The current tracer, if there is one and it's a `T`.
+    pub fn tracer<T>(&self) -> Option<&T>
+    where
+        T: Tracer<M::Word> + 'static,
+    {
+        let tracer: &dyn Any = self.tracer.as_deref()?;
+
+        tracer.downcast_ref()
+    }
+
+    /// Detach the current tracer and return it, if there is one and it's a `T`.
+    pub fn take_tracer<T>(&mut self) -> Option<T>
+    where
+        T: Tracer<M::Word> + 'static,
+    {
+        self.tracer::<T>()?;
+        let tracer: Box<dyn Any> = self.tracer.take()?;
+
+        tracer.downcast().ok().map(|tracer| *tracer)
+    }
+
+    /// Decode the instruction at the instruction pointer without executing it.
+    #[inline(always)]
+    pub fn current_instruction(&mut self) -> Result<Instruction<M::Word>, IntcodeError> {
+        if !self.cache_instructions {
+            return self.decode(self.ip);
+        }
+
+        match self.decoded.get(self.ip) {
+            Some(Some(instruction)) => Ok(*instruction),
+            _ => self.decode_and_cache(self.ip),
         }
     }
 
-    fn value(&mut self, parameter: &Parameter, relative_base_offset: usize) -> isize {
-        match parameter {
-            &Parameter::Immediate(value) => value,
-            &Parameter::Position(position) => self.read(position),
-            &Parameter::Relative(_) => self.read(parameter.address(relative_base_offset)),
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base_offset(&self) -> isize {
+        self.relative_base_offset as isize
+    }
+
+    /// The most recent output that hasn't been taken.
+    pub fn last_output(&self) -> Option<M::Word> {
+        self.outputs.last().copied()
+    }
+
+    /// All outputs that haven't been taken, oldest first.
+    pub fn all_outputs(&self) -> &[M::Word] {
+        &self.outputs
+    }
+
+    /// Take the oldest output that hasn't been taken yet.
+    pub fn take_output(&mut self) -> Option<M::Word> {
+        if self.outputs.is_empty() {
+            None
+        } else {
+            Some(self.outputs.remove(0))
         }
     }
-}
 
-const INITIAL_MEMORY_SIZE: usize = 10_000;
+    /// Take all outputs that haven't been taken yet, oldest first.
+    pub fn drain_outputs(&mut self) -> impl Iterator<Item = M::Word> + '_ {
+        self.outputs.drain(..)
+    }
 
-pub struct Computer<F>
-where
-    F: FnMut() -> Option<isize>,
-{
-    input: Option<F>,
-    outputs: Vec<isize>,
-    program: GrowableMemory,
-    did_halt: bool,
-    ip: usize,
-    relative_base_offset: usize,
-}
+    /// Enable or disable caching of decoded instructions, which is enabled by default.
+    ///
+    /// Cached instructions are invalidated whenever memory they were decoded from is written,
+    /// so self modifying programs behave the same either way.
+    pub fn set_instruction_cache(&mut self, enabled: bool) {
+        self.cache_instructions = enabled;
+        self.decoded = vec![];
+    }
 
-impl<F> Computer<F>
-where
-    F: FnMut() -> Option<isize>,
-{
-    pub fn new(program: Vec<isize>) -> Self {
-        Self {
-            input: None,
-            outputs: vec![],
-            program: GrowableMemory::new(program, INITIAL_MEMORY_SIZE),
-            did_halt: false,
-            ip: 0,
-            relative_base_offset: 0,
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    /// Mutable access to memory, which discards all cached instructions and compiled blocks.
+    pub fn memory_mut(&mut self) -> &mut M {
+        self.discard_compiled();
+
+        &mut self.memory
+    }
+
+    /// Capture everything needed to resume the program later, except the input source.
+    pub fn snapshot(&self) -> Snapshot<M::Word> {
+        let mut memory: Vec<(usize, Vec<M::Word>)> = vec![];
+
+        for (address, words) in self.memory.segments() {
+            match memory.last_mut() {
+                Some((start, run)) if *start + run.len() == address => run.extend(words),
+                _ => memory.push((address, words.to_vec())),
+            }
+        }
+
+        for (_, run) in &mut memory {
+            let used = run
+                .iter()
+                .rposition(|&word| word != M::Word::ZERO)
+                .map_or(0, |last| last + 1);
+            run.truncate(used);
+        }
+        memory.retain(|(_, run)| !run.is_empty());
+
+        Snapshot {
+            memory,
+            ip: self.ip,
+            relative_base_offset: self.relative_base_offset,
+            halted: self.did_halt,
+            inputs: self.input_queue.iter().cloned().collect(),
+            outputs: self.outputs.clone(),
         }
     }
 
-    pub fn with_input(program: Vec<isize>, input: F) -> Self {
-        Self {
-            input: Some(input),
-            outputs: vec![],
-            program: GrowableMemory::new(program, INITIAL_MEMORY_SIZE),
-            did_halt: false,
-            ip: 0,
-            relative_base_offset: 0,
+    /// Replace the state of the computer with `snapshot`, the input source is kept.
+    ///
+    /// Fails if the memory can't hold the snapshot or a memory run reaches past `usize::MAX`, in
+    /// which case the computer is left cleared.
+    pub fn restore(&mut self, snapshot: &Snapshot<M::Word>) -> Result<(), IntcodeErrorReason> {
+        self.memory.clear();
+        self.discard_compiled();
+        self.ip = snapshot.ip;
+        self.relative_base_offset = snapshot.relative_base_offset;
+        self.did_halt = snapshot.halted;
+        self.input_queue = snapshot.inputs.iter().cloned().collect();
+        self.outputs = snapshot.outputs.clone();
+
+        for (start, run) in &snapshot.memory {
+            for (offset, &word) in run.iter().enumerate() {
+                let address =
+                    start
+                        .checked_add(offset)
+                        .ok_or(IntcodeErrorReason::AddressOutOfRange(
+                            *start as i128 + offset as i128,
+                        ))?;
+                self.memory.write(address, word)?;
+            }
         }
+
+        Ok(())
     }
 
-    pub fn is_halted(&self) -> bool {
-        self.did_halt
+    fn discard_compiled(&mut self) {
+        self.decoded.clear();
+        self.threaded = None;
+        self.code_words.clear();
+        self.modified_code = None;
     }
 
-    pub fn set_input(&mut self, input: F) {
-        self.input = Some(input);
+    fn decode(&self, address: usize) -> Result<Instruction<M::Word>, IntcodeError> {
+        let words = [
+            self.memory.read(address),
+            self.memory.read(address + 1),
+            self.memory.read(address + 2),
+            self.memory.read(address + 3),
+        ];
+
+        match &self.extensions {
+            Some(extensions) => Instruction::parse_extended(address, &words, extensions),
+            None => Instruction::parse(address, &words),
+        }
     }
 
-    pub fn run_until_halt_or_paused(&mut self, stop_on_output: bool) {
-        while self.ip < self.program.len() {
-            let parsed_instruction = Instruction::parse(&self.program.read_instruction(self.ip));
+    #[cold]
+    #[inline(never)]
+    fn decode_and_cache(&mut self, address: usize) -> Result<Instruction<M::Word>, IntcodeError> {
+        let instruction = self.decode(address)?;
 
-            match self.execute_instruction(&parsed_instruction, stop_on_output) {
-                ExecutionState::Halt => {
-                    self.did_halt = true;
-                    break;
-                }
-                ExecutionState::Pause => {
-                    break;
-                }
-                ExecutionState::Continue => (),
+        if address < INSTRUCTION_CACHE_LIMIT {
+            if address >= self.decoded.len() {
+                let len = (address + 1).max(self.decoded.len() * 2);
+                self.decoded.resize(len.min(INSTRUCTION_CACHE_LIMIT), None);
             }
+            self.decoded[address] = Some(instruction);
         }
+
+        Ok(instruction)
     }
 
-    pub fn last_output(&self) -> Option<isize> {
-        self.outputs.last().map(|v| *v)
+    #[inline(always)]
+    pub(crate) fn value(
+        &self,
+        parameter: &Parameter<M::Word>,
+    ) -> Result<M::Word, IntcodeErrorReason> {
+        match *parameter {
+            Parameter::Immediate(value) => Ok(value),
+            Parameter::Position(position) => Ok(self.memory.read(position)),
+            Parameter::Relative(_) => Ok(self
+                .memory
+                .read(parameter.address(self.relative_base_offset)?)),
+        }
     }
 
-    pub fn all_outputs(&self) -> &[isize] {
-        &self.outputs
+    /// Write to memory, evicting cached instructions that `address` is part of. With `COMPILED`,
+    /// writes to compiled code are flagged too, which only matters once blocks were compiled.
+    #[inline(always)]
+    pub(crate) fn write<const COMPILED: bool>(
+        &mut self,
+        address: usize,
+        value: M::Word,
+    ) -> Result<(), IntcodeErrorReason> {
+        // Self modifying programs often store the value a word already holds, which doesn't
+        // need to evict any cached instructions
+        if self.memory.read(address) == value {
+            return Ok(());
+        }
+
+        self.memory.write(address, value)?;
+
+        let end = (address + 1).min(self.decoded.len());
+        let start = address.saturating_sub(3).min(end);
+        for decoded in &mut self.decoded[start..end] {
+            *decoded = None;
+        }
+
+        if COMPILED && self.code_words.get(address) == Some(&true) {
+            self.modified_code = Some(address);
+        }
+
+        Ok(())
     }
 
-    pub fn memory(&mut self) -> &mut GrowableMemory {
-        &mut self.program
+    #[inline(always)]
+    pub(crate) fn execute_next_instruction<const TRACE: bool, const COMPILED: bool>(
+        &mut self,
+        stop_on_output: bool,
+    ) -> Result<Option<ExecutionState<M::Word>>, IntcodeError> {
+        let parsed_instruction = self.current_instruction()?;
+        let state =
+            self.execute_instruction::<TRACE, COMPILED>(&parsed_instruction, stop_on_output)?;
+
+        match state {
+            Some(ExecutionState::NeedsInput) => return Ok(state),
+            Some(ExecutionState::Halted) => self.did_halt = true,
+            _ => (),
+        }
+        self.steps += 1;
+
+        Ok(state)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn execute_extended(&mut self, extended: &Extended<M::Word>) -> Result<Effect, IntcodeError> {
+        let extensions = Arc::clone(
+            self.extensions
+                .as_ref()
+                .expect("Extended instructions are only decoded with extensions"),
+        );
+
+        extensions.execute(extended.opcode, self, extended.parameters())
+    }
+
+    pub(crate) fn read_input(&mut self) -> Option<M::Word> {
+        match self.input_queue.pop_front() {
+            Some(value) => Some(value),
+            None => self
+                .input_source
+                .as_mut()
+                .and_then(|source| source.next_input()),
+        }
     }
 
-    fn read_input(&mut self) -> Option<isize> {
-        self.input.as_mut().and_then(|input| input())
+    pub(crate) fn fault(&self, parameter: usize, reason: IntcodeErrorReason) -> IntcodeError {
+        IntcodeError {
+            parameter: Some(parameter),
+            ..self.instruction_fault(reason)
+        }
     }
 
-    fn execute_instruction(
+    /// A fault of the instruction at the instruction pointer as a whole, like an overflow.
+    pub(crate) fn instruction_fault(&self, reason: IntcodeErrorReason) -> IntcodeError {
+        IntcodeError {
+            ip: self.ip,
+            opcode: self.memory.read(self.ip).to_i128(),
+            parameter: None,
+            reason,
+        }
+    }
+
+    fn execute_instruction<const TRACE: bool, const COMPILED: bool>(
         &mut self,
-        instruction: &Instruction,
+        instruction: &Instruction<M::Word>,
         stop_on_output: bool,
-    ) -> ExecutionState {
+    ) -> Result<Option<ExecutionState<M::Word>>, IntcodeError> {
         let relative_base_offset = self.relative_base_offset;
+        let mut reads = [None; 3];
+        let mut written = None;
+
+        macro_rules! value {
+            ($index:expr, $parameter:expr) => {
+                match self.value($parameter) {
+                    Ok(value) => {
+                        if TRACE {
+                            reads[$index] = Some(value);
+                        }
+
+                        value
+                    }
+                    Err(reason) => return Err(self.fault($index, reason)),
+                }
+            };
+        }
+
+        macro_rules! address {
+            ($index:expr, $parameter:expr) => {
+                match $parameter.address(relative_base_offset) {
+                    Ok(address) => address,
+                    Err(reason) => return Err(self.fault($index, reason)),
+                }
+            };
+        }
+
+        macro_rules! store {
+            ($index:expr, $address:expr, $value:expr) => {{
+                let (address, value) = ($address, $value);
+
+                if let Err(reason) = self.write::<COMPILED>(address, value) {
+                    return Err(self.fault($index, reason));
+                }
+
+                if TRACE {
+                    written = Some((address, value));
+                }
+            }};
+        }
 
         let (new_ip, new_state) = match instruction {
             Instruction::Op(op, arg1, arg2, arg3) => {
-                let a1 = self.program.value(arg1, relative_base_offset);
-                let a2 = self.program.value(arg2, relative_base_offset);
-                let a3 = arg3.address(relative_base_offset);
+                let a1 = value!(0, arg1);
+                let a2 = value!(1, arg2);
+                let a3 = address!(2, arg3);
 
-                match op {
-                    Op::Add => self.program.write(a3, a1 + a2),
-                    Op::Multiply => self.program.write(a3, a1 * a2),
+                match op.apply(a1, a2, self.overflow) {
+                    Some(value) => store!(2, a3, value),
+                    None => {
+                        return Err(self.instruction_fault(IntcodeErrorReason::Overflow));
+                    }
                 }
 
                 (None, None)
             }
             Instruction::ConditionalJump(condition, arg1, arg2) => {
-                let a1 = self.program.value(arg1, relative_base_offset);
-                let a2 = self.program.value(arg2, relative_base_offset);
+                let a1 = value!(0, arg1);
+                let a2 = value!(1, arg2);
 
-                match condition {
-                    JumpCondition::IfTrue => {
-                        if a1 != 0 {
-                            (Some(a2 as usize), None)
-                        } else {
-                            (None, None)
-                        }
-                    }
-                    JumpCondition::IfFalse => {
-                        if a1 == 0 {
-                            (Some(a2 as usize), None)
-                        } else {
-                            (None, None)
-                        }
+                let should_jump = match condition {
+                    JumpCondition::IfTrue => a1 != M::Word::ZERO,
+                    JumpCondition::IfFalse => a1 == M::Word::ZERO,
+                };
+
+                if !should_jump {
+                    (None, None)
+                } else {
+                    match to_address(a2, false) {
+                        Ok(target) => (Some(target as usize), None),
+                        Err(reason) => return Err(self.fault(1, reason)),
                     }
                 }
             }
             Instruction::Compare(op, arg1, arg2, arg3) => {
-                let a1 = self.program.value(arg1, relative_base_offset);
-                let a2 = self.program.value(arg2, relative_base_offset);
-                let a3 = arg3.address(relative_base_offset);
+                let a1 = value!(0, arg1);
+                let a2 = value!(1, arg2);
+                let a3 = address!(2, arg3);
 
                 match op {
                     ComparisonOp::LessThan => {
                         if a1 < a2 {
-                            self.program.write(a3, 1);
+                            store!(2, a3, M::Word::ONE);
                         } else {
-                            self.program.write(a3, 0);
+                            store!(2, a3, M::Word::ZERO);
                         }
                     }
                     ComparisonOp::Equal => {
                         if a1 == a2 {
-                            self.program.write(a3, 1);
+                            store!(2, a3, M::Word::ONE);
                         } else {
-                            self.program.write(a3, 0);
+                            store!(2, a3, M::Word::ZERO);
                         }
                     }
                 }
@@ -360,43 +1192,428 @@ where
                 (None, None)
             }
             Instruction::Input(arg1) => {
+                let a1 = address!(0, arg1);
                 let next_input = self.read_input();
 
                 match next_input {
                     None => {
-                        // We need to wait
-                        (None, Some(ExecutionState::Pause))
+                        // We need to wait, the instruction is retried on the next run
+                        (Some(self.ip), Some(ExecutionState::NeedsInput))
                     }
                     Some(input) => {
-                        let a1 = arg1.address(relative_base_offset);
-                        self.program.write(a1 as usize, input);
+                        store!(0, a1, input);
 
                         (None, None)
                     }
                 }
             }
             Instruction::Output(arg1) => {
-                let a1 = self.program.value(arg1, relative_base_offset);
+                let a1 = value!(0, arg1);
 
                 self.outputs.push(a1);
 
                 if stop_on_output {
-                    (None, Some(ExecutionState::Pause))
+                    (None, Some(ExecutionState::Output(a1)))
                 } else {
                     (None, None)
                 }
             }
             Instruction::RelativeBaseAdjust(arg1) => {
-                let a1 = self.program.value(arg1, relative_base_offset);
-                self.relative_base_offset = ((self.relative_base_offset as isize) + a1) as usize;
+                let a1 = value!(0, arg1);
+                let a1 = match to_address(a1, true) {
+                    Ok(adjustment) => adjustment,
+                    Err(reason) => return Err(self.fault(0, reason)),
+                };
+                self.relative_base_offset =
+                    match adjusted_relative_base(self.relative_base_offset, a1) {
+                        Ok(relative_base_offset) => relative_base_offset,
+                        Err(reason) => return Err(self.fault(0, reason)),
+                    };
 
                 (None, None)
             }
-            Instruction::Halt => (Some(self.ip), Some(ExecutionState::Halt)),
+            Instruction::Halt => (Some(self.ip), Some(ExecutionState::Halted)),
+            Instruction::Extended(extended) => match self.execute_extended(extended)? {
+                Effect::Continue => (None, None),
+                Effect::Jump(target) => (Some(target), None),
+                Effect::Yield => (None, Some(ExecutionState::Yielded)),
+                Effect::Halt => (Some(self.ip), Some(ExecutionState::Halted)),
+            },
         };
 
+        if TRACE && new_state != Some(ExecutionState::NeedsInput) {
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.trace(&TraceEvent {
+                    ip: self.ip,
+                    instruction: *instruction,
+                    reads,
+                    written,
+                });
+            }
+        }
+
         self.ip = new_ip.unwrap_or_else(|| self.ip + instruction.length());
 
-        new_state.unwrap_or(ExecutionState::Continue)
+        Ok(new_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        input_with_initial_value, Backend, Computer, ExecutionState, IntcodeError,
+        IntcodeErrorReason, Memory, Overflow, PagedMemory, PAGE_SIZE,
+    };
+
+    fn run(program: &[isize]) -> Result<ExecutionState, IntcodeError> {
+        let mut computer = Computer::with_input(program.to_vec(), || Some(0));
+
+        computer.try_run(false)
+    }
+
+    #[test]
+    fn test_try_run_halts() {
+        assert_eq!(run(&[1101, 2, 3, 5, 99, 0]), Ok(ExecutionState::Halted));
+    }
+
+    #[test]
+    fn test_execution_states() {
+        let mut computer = Computer::new(vec![3, 9, 4, 9, 104, 7, 99, 0, 0, 0]);
+
+        assert_eq!(
+            computer.run_until_halt_or_paused(true),
+            ExecutionState::NeedsInput
+        );
+
+        computer.push_input(5);
+        assert_eq!(
+            computer.run_until_halt_or_paused(true),
+            ExecutionState::Output(5)
+        );
+        assert_eq!(
+            computer.run_until_halt_or_paused(true),
+            ExecutionState::Output(7)
+        );
+        assert_eq!(
+            computer.run_until_halt_or_paused(true),
+            ExecutionState::Halted
+        );
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn test_input_queue_and_outputs() {
+        // Outputs the sum of three inputs, then the first input again
+        let program = vec![
+            3, 100, 3, 101, 3, 102, 1, 100, 101, 103, 1, 102, 103, 103, 4, 103, 4, 100, 99,
+        ];
+        let mut computer = Computer::with_input(program, || Some(10));
+        computer.push_inputs(vec![1, 2]);
+
+        assert_eq!(
+            computer.run_until_halt_or_paused(false),
+            ExecutionState::Halted
+        );
+        assert_eq!(computer.take_output(), Some(13));
+        assert_eq!(computer.drain_outputs().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(computer.take_output(), None);
+        assert_eq!(computer.all_outputs(), &[] as &[isize]);
+    }
+
+    #[test]
+    fn test_computers_with_different_inputs_share_a_type() {
+        let program = vec![3, 5, 4, 5, 99, 0];
+        let mut computers = [
+            Computer::with_input(program.clone(), || Some(1)),
+            Computer::with_input(program.clone(), input_with_initial_value(2, || None)),
+            Computer::new(program),
+        ];
+        computers[2].push_input(3);
+
+        let outputs: Vec<_> = computers
+            .iter_mut()
+            .map(|computer| computer.run_until_halt_or_paused(true))
+            .collect();
+
+        assert_eq!(
+            outputs,
+            vec![
+                ExecutionState::Output(1),
+                ExecutionState::Output(2),
+                ExecutionState::Output(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let mut computer = Computer::new(vec![1101, 2, 3, 7, 3, 7, 104, 0, 99]);
+
+        assert_eq!(computer.step(), Ok(None));
+        assert_eq!(computer.ip(), 4);
+        assert_eq!(computer.memory().read(7), 5);
+
+        assert_eq!(computer.step(), Ok(Some(ExecutionState::NeedsInput)));
+        assert_eq!(computer.ip(), 4);
+
+        computer.push_input(9);
+        assert_eq!(computer.step(), Ok(None));
+        assert_eq!(computer.step(), Ok(Some(ExecutionState::Output(9))));
+        assert_eq!(computer.step(), Ok(Some(ExecutionState::Halted)));
+        assert!(computer.is_halted());
+    }
+
+    #[test]
+    fn test_step_budget() {
+        // Counts down from the input, outputting every value
+        let program = vec![3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0];
+
+        for &backend in &[Backend::Interpreter, Backend::Compiled] {
+            let mut computer = Computer::new(program.clone());
+            computer.set_backend(backend);
+
+            assert_eq!(computer.run_for(10, false), Ok(ExecutionState::NeedsInput));
+            assert_eq!(computer.steps(), 0);
+
+            computer.push_input(3);
+            assert_eq!(
+                computer.run_for(5, false),
+                Ok(ExecutionState::StepLimitReached)
+            );
+            assert_eq!(computer.steps(), 5);
+            assert_eq!(computer.all_outputs(), &[3, 2]);
+
+            assert_eq!(computer.run_for(100, false), Ok(ExecutionState::Halted));
+            assert_eq!(computer.steps(), 11);
+            assert_eq!(computer.all_outputs(), &[3, 2, 1]);
+        }
+    }
+
+    #[test]
+    fn test_deadline() {
+        for &backend in &[Backend::Interpreter, Backend::Compiled] {
+            // Jumps to itself forever
+            let mut computer = Computer::new(vec![1105, 1, 0]);
+            computer.set_backend(backend);
+
+            assert_eq!(
+                computer.run_for(1000, false),
+                Ok(ExecutionState::StepLimitReached)
+            );
+            assert_eq!(computer.steps(), 1000);
+
+            computer.set_deadline(Some(Instant::now() + Duration::from_millis(20)));
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::DeadlineReached));
+            assert!(computer.steps() > 1000);
+
+            let mut computer = Computer::new(vec![104, 7, 99]);
+            computer.set_backend(backend);
+            computer.set_deadline(Some(Instant::now() + Duration::from_secs(60)));
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        }
+    }
+
+    #[test]
+    fn test_instruction_cache_is_invalidated_by_writes() {
+        // Outputs 1, rewrites the operand of its own output instruction to 7 and loops once
+        let program = vec![
+            104, 1, 1101, 0, 7, 1, 1005, 17, 16, 1101, 0, 1, 17, 1105, 1, 0, 99, 0,
+        ];
+
+        for &cache in &[true, false] {
+            let mut computer = Computer::new(program.clone());
+            computer.set_instruction_cache(cache);
+
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+            assert_eq!(computer.all_outputs(), &[1, 7]);
+
+            let mut computer = Computer::new(program.clone());
+            computer.set_instruction_cache(cache);
+            assert_eq!(computer.step(), Ok(Some(ExecutionState::Output(1))));
+
+            computer.memory_mut().write(0, 99).unwrap();
+            assert_eq!(computer.step(), Ok(None));
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+            assert_eq!(computer.ip(), 0);
+        }
+    }
+
+    #[test]
+    fn test_large_addresses() {
+        let high = (usize::MAX / 2) as isize;
+        // Copies the input to, and back from, an address near the top of memory
+        let program = vec![109, high - 1, 203, 1, 2201, 1, 0, 11, 4, 11, 99, 0];
+        let mut computer = Computer::new(program.clone());
+        computer.push_input(42);
+
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[42]);
+        assert_eq!(computer.memory().read(high as usize), 42);
+        assert_eq!(computer.memory().allocated(), 2 * PAGE_SIZE);
+
+        let mut computer = Computer::new(program);
+        computer.memory_mut().set_limit(Some(PAGE_SIZE));
+        computer.push_input(42);
+
+        assert_eq!(
+            computer.try_run(false),
+            Err(IntcodeError {
+                ip: 2,
+                opcode: 203,
+                parameter: Some(0),
+                reason: IntcodeErrorReason::MemoryLimitExceeded(high as usize),
+            })
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        // Outputs double the input, using the relative base for the temporary
+        let mut computer = Computer::new(vec![109, 20, 203, 0, 22201, 0, 0, 1, 204, 1, 99]);
+        assert_eq!(computer.step(), Ok(None));
+
+        let snapshot = computer.snapshot();
+        let branches: Vec<_> = (1..=3)
+            .map(|input| {
+                let mut branch = Computer::new(vec![]);
+                branch.restore(&snapshot).unwrap();
+                branch.push_input(input);
+
+                branch.run_until_halt_or_paused(true)
+            })
+            .collect();
+
+        assert_eq!(
+            branches,
+            vec![
+                ExecutionState::Output(2),
+                ExecutionState::Output(4),
+                ExecutionState::Output(6)
+            ]
+        );
+
+        computer.push_input(10);
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        computer.restore(&snapshot).unwrap();
+        assert!(!computer.is_halted());
+        assert_eq!(computer.relative_base_offset(), 20);
+        assert_eq!(computer.all_outputs(), &[]);
+        assert_eq!(computer.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_word_width_and_overflow() {
+        // Outputs the product of two inputs, 2^62 * 4 overflows an i64
+        let program: [i64; 14] = [3, 11, 3, 12, 2, 11, 12, 13, 4, 13, 99, 0, 0, 0];
+        let inputs = [1 << 62, 4];
+
+        let mut computer = Computer::with_memory(PagedMemory::new(&program));
+        computer.push_inputs(inputs.iter().copied());
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[0]);
+
+        let mut computer = Computer::with_memory(PagedMemory::new(&program));
+        computer.set_overflow(Overflow::Trap);
+        computer.push_inputs(inputs.iter().copied());
+        assert_eq!(
+            computer.try_run(false),
+            Err(IntcodeError {
+                ip: 4,
+                opcode: 2,
+                parameter: None,
+                reason: IntcodeErrorReason::Overflow,
+            })
+        );
+
+        let mut computer = Computer::with_memory(PagedMemory::new(&program.map(i128::from)));
+        computer.set_overflow(Overflow::Trap);
+        computer.push_inputs(inputs.iter().map(|&input| i128::from(input)));
+        assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+        assert_eq!(computer.all_outputs(), &[1 << 64]);
+
+        let mut computer = Computer::with_memory(PagedMemory::<i128>::new(&[1105, 1, 1 << 100]));
+        assert_eq!(
+            computer.try_run(false).unwrap_err().reason,
+            IntcodeErrorReason::AddressOutOfRange(1 << 100)
+        );
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        assert_eq!(
+            run(&[1101, 2, 3, 5, 42]),
+            Err(IntcodeError {
+                ip: 4,
+                opcode: 42,
+                parameter: None,
+                reason: IntcodeErrorReason::InvalidOpcode,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_mode() {
+        assert_eq!(
+            run(&[3101, 2, 3, 5, 99]),
+            Err(IntcodeError {
+                ip: 0,
+                opcode: 3101,
+                parameter: Some(1),
+                reason: IntcodeErrorReason::InvalidMode(3),
+            })
+        );
+    }
+
+    #[test]
+    fn test_immediate_write() {
+        assert_eq!(
+            run(&[3, 3, 11101, 2, 3, 5, 99]),
+            Err(IntcodeError {
+                ip: 2,
+                opcode: 11101,
+                parameter: Some(2),
+                reason: IntcodeErrorReason::ImmediateWrite,
+            })
+        );
+        assert_eq!(
+            run(&[103, 5, 99]).unwrap_err().reason,
+            IntcodeErrorReason::ImmediateWrite
+        );
+    }
+
+    #[test]
+    fn test_negative_address() {
+        assert_eq!(
+            run(&[1, -1, 0, 0, 99]),
+            Err(IntcodeError {
+                ip: 0,
+                opcode: 1,
+                parameter: Some(0),
+                reason: IntcodeErrorReason::NegativeAddress(-1),
+            })
+        );
+        assert_eq!(
+            run(&[109, -5, 2201, 1, 0, 0, 99]),
+            Err(IntcodeError {
+                ip: 2,
+                opcode: 2201,
+                parameter: Some(0),
+                reason: IntcodeErrorReason::NegativeAddress(-4),
+            })
+        );
+        assert_eq!(
+            run(&[1105, 1, -3]).unwrap_err().reason,
+            IntcodeErrorReason::NegativeAddress(-3)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid opcode `42` at address 0")]
+    fn test_run_until_halt_or_paused_panics_on_fault() {
+        let mut computer = Computer::with_input(vec![42], || Some(0));
+
+        computer.run_until_halt_or_paused(false);
     }
 }
@@ -0,0 +1,375 @@
+//! Conformance tests for the instruction set, run on every way a [`Computer`] can execute.
+//!
+//! Every opcode is executed in every legal combination of parameter modes, followed by the
+//! example programs from the puzzle texts and the corners of the memory model.
+use super::{
+    Backend, Computer, ExecutionState, IntcodeError, IntcodeErrorReason, Memory, PAGE_SIZE,
+};
+
+/// Backend and instruction cache setting of every way to execute a program.
+const CONFIGURATIONS: [(Backend, bool); 3] = [
+    (Backend::Interpreter, false),
+    (Backend::Interpreter, true),
+    (Backend::Compiled, true),
+];
+
+/// The relative base of the programs built by [`program`].
+const BASE: isize = 100;
+/// Position mode parameters read from these addresses, indexed by parameter.
+const POSITIONS: [isize; 2] = [50, 51];
+/// Relative mode parameters read from these offsets, indexed by parameter.
+const OFFSETS: [isize; 2] = [-4, 3];
+/// Where position mode parameters write to.
+const WRITE_POSITION: isize = 60;
+/// The offset relative mode parameters write to.
+const WRITE_OFFSET: isize = -2;
+/// Jumps that are taken end up here, at an instruction outputting 1.
+const TARGET: isize = 30;
+
+/// Run `program` with `inputs` to completion in every configuration and check each computer.
+fn check<F>(program: &[isize], inputs: &[isize], check: F)
+where
+    F: Fn(&Computer),
+{
+    for &(backend, cache_instructions) in &CONFIGURATIONS {
+        let mut computer = Computer::new(program.to_vec());
+        computer.set_backend(backend);
+        computer.set_instruction_cache(cache_instructions);
+        computer.push_inputs(inputs.iter().copied());
+
+        assert_eq!(
+            computer.try_run(false),
+            Ok(ExecutionState::Halted),
+            "{:?} with the instruction cache {}",
+            backend,
+            if cache_instructions { "on" } else { "off" }
+        );
+        check(&computer);
+    }
+}
+
+/// Every combination of `modes` for `reads` read parameters and an optional write parameter.
+fn mode_combinations(reads: usize, writes: bool) -> Vec<Vec<isize>> {
+    let mut combinations = vec![vec![]];
+
+    for _ in 0..reads {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|modes: Vec<isize>| {
+                (0..3).map(move |mode| {
+                    let mut modes = modes.clone();
+                    modes.push(mode);
+                    modes
+                })
+            })
+            .collect();
+    }
+
+    if writes {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|modes| {
+                vec![0, 2].into_iter().map(move |mode| {
+                    let mut modes = modes.clone();
+                    modes.push(mode);
+                    modes
+                })
+            })
+            .collect();
+    }
+
+    combinations
+}
+
+/// A program that sets the relative base to [`BASE`] and executes a single instruction with
+/// `opcode` and `modes`, reading `values` and writing with the last mode if there's one more mode
+/// than values. It outputs 0 and halts after the instruction, or outputs 1 and halts at
+/// [`TARGET`].
+fn program(opcode: isize, modes: &[isize], values: &[isize]) -> Vec<isize> {
+    let mut program = vec![0; 200];
+    program[..2].copy_from_slice(&[109, BASE]);
+
+    let mut instruction = vec![opcode];
+    for (index, &mode) in modes.iter().enumerate() {
+        instruction[0] += mode * 10_isize.pow(index as u32 + 2);
+
+        let word = match (values.get(index), mode) {
+            (Some(&value), 0) => {
+                program[POSITIONS[index] as usize] = value;
+                POSITIONS[index]
+            }
+            (Some(&value), 1) => value,
+            (Some(&value), _) => {
+                program[(BASE + OFFSETS[index]) as usize] = value;
+                OFFSETS[index]
+            }
+            (None, 0) => WRITE_POSITION,
+            (None, _) => WRITE_OFFSET,
+        };
+        instruction.push(word);
+    }
+
+    let end = 2 + instruction.len();
+    program[2..end].copy_from_slice(&instruction);
+    program[end..end + 3].copy_from_slice(&[104, 0, 99]);
+    program[TARGET as usize..TARGET as usize + 3].copy_from_slice(&[104, 1, 99]);
+
+    program
+}
+
+/// The address an instruction built by [`program`] writes to in the mode of its last parameter.
+fn written_address(modes: &[isize]) -> usize {
+    match modes.last() {
+        Some(0) => WRITE_POSITION as usize,
+        _ => (BASE + WRITE_OFFSET) as usize,
+    }
+}
+
+#[test]
+fn test_arithmetic_and_comparisons() {
+    type Expected = fn(isize, isize) -> isize;
+
+    let cases: [(isize, Expected); 4] = [
+        (1, |a, b| a + b),
+        (2, |a, b| a * b),
+        (7, |a, b| (a < b) as isize),
+        (8, |a, b| (a == b) as isize),
+    ];
+
+    for &(opcode, expected) in &cases {
+        for modes in mode_combinations(2, true) {
+            for &(a, b) in &[(6, 7), (7, 7), (-8, 3)] {
+                check(&program(opcode, &modes, &[a, b]), &[], |computer| {
+                    assert_eq!(
+                        computer.memory().read(written_address(&modes)),
+                        expected(a, b),
+                        "Opcode {} with modes {:?} on {} and {}",
+                        opcode,
+                        modes,
+                        a,
+                        b
+                    );
+                    assert_eq!(computer.all_outputs(), &[0]);
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn test_input_and_output() {
+    for modes in mode_combinations(0, true) {
+        check(&program(3, &modes, &[]), &[-42], |computer| {
+            assert_eq!(computer.memory().read(written_address(&modes)), -42);
+        });
+    }
+
+    for modes in mode_combinations(1, false) {
+        check(&program(4, &modes, &[-42]), &[], |computer| {
+            assert_eq!(computer.all_outputs(), &[-42, 0], "Modes {:?}", modes);
+        });
+    }
+}
+
+#[test]
+fn test_jumps() {
+    for &(opcode, jumps_if_zero) in &[(5, false), (6, true)] {
+        for modes in mode_combinations(2, false) {
+            for &condition in &[0, 1, -3] {
+                let jumps = (condition == 0) == jumps_if_zero;
+
+                check(
+                    &program(opcode, &modes, &[condition, TARGET]),
+                    &[],
+                    |computer| {
+                        assert_eq!(
+                            computer.all_outputs(),
+                            &[jumps as isize],
+                            "Opcode {} with modes {:?} on {}",
+                            opcode,
+                            modes,
+                            condition
+                        );
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_relative_base_adjustment() {
+    for modes in mode_combinations(1, false) {
+        for &adjustment in &[5, 0, -7] {
+            check(&program(9, &modes, &[adjustment]), &[], |computer| {
+                assert_eq!(computer.relative_base_offset(), BASE + adjustment);
+            });
+        }
+    }
+}
+
+#[test]
+fn test_halt() {
+    check(&[99, 104, 1], &[], |computer| {
+        assert_eq!(computer.ip(), 0);
+        assert!(computer.is_halted());
+        assert_eq!(computer.all_outputs(), &[] as &[isize]);
+    });
+}
+
+#[test]
+fn test_self_modifying_code() {
+    // Multiplies the 33 at address 4 by 3, which turns it into the halt instruction
+    check(&[1002, 4, 3, 4, 33], &[], |computer| {
+        assert_eq!(computer.memory().read(4), 99);
+    });
+
+    // Outputs its counter until it reaches 10, turning the increment into a doubling after the
+    // first pass
+    let program = [
+        4, 20, // out [20]
+        1, 20, 21, 20, // add [20], [21], [20], becomes a mul
+        1101, 0, 2, 2, // add #0, #2, [2]
+        1007, 20, 10, 22, // lt [20], #10, [22]
+        1005, 22, 0, // jnz [22], #0
+        99, 0, 0, 1, 2, 0,
+    ];
+    check(&program, &[], |computer| {
+        assert_eq!(computer.all_outputs(), &[1, 3, 6]);
+        assert_eq!(computer.memory().read(2), 2);
+        assert_eq!(computer.memory().read(20), 12);
+    });
+}
+
+#[test]
+fn test_far_writes() {
+    // Past the first page, and past the pages kept in a directory rather than a map
+    for &address in &[PAGE_SIZE as isize * 3 + 7, 1 << 40] {
+        let program = [1101, 7, 8, address, 4, address, 99];
+
+        check(&program, &[], |computer| {
+            assert_eq!(computer.memory().read(address as usize), 15);
+            assert_eq!(computer.all_outputs(), &[15]);
+        });
+
+        // The same through the relative base
+        let program = [109, address, 21101, 7, 8, 5, 204, 5, 99];
+
+        check(&program, &[], |computer| {
+            assert_eq!(computer.memory().read(address as usize + 5), 15);
+            assert_eq!(computer.all_outputs(), &[15]);
+        });
+    }
+}
+
+#[test]
+fn test_negative_relative_base() {
+    // Moves the relative base below zero and reaches the program through positive offsets
+    let program = [109, -10, 204, 10, 21101, 3, 4, 21, 204, 21, 99, 0];
+
+    check(&program, &[], |computer| {
+        assert_eq!(computer.relative_base_offset(), -10);
+        assert_eq!(computer.memory().read(11), 7);
+        assert_eq!(computer.all_outputs(), &[109, 7]);
+    });
+}
+
+#[test]
+fn test_relative_base_overflow() {
+    // A relative parameter and a relative base adjustment past the range of `isize`
+    let cases = [
+        (
+            [109, -isize::MAX, 209, -isize::MAX, 99],
+            IntcodeErrorReason::NegativeAddress(-2 * isize::MAX as i128),
+        ),
+        (
+            [109, isize::MAX, 109, 1, 99],
+            IntcodeErrorReason::AddressOutOfRange(isize::MAX as i128 + 1),
+        ),
+    ];
+
+    for &(program, reason) in &cases {
+        for &(backend, cache_instructions) in &CONFIGURATIONS {
+            let mut computer = Computer::new(program.to_vec());
+            computer.set_backend(backend);
+            computer.set_instruction_cache(cache_instructions);
+
+            assert_eq!(
+                computer.try_run(false),
+                Err(IntcodeError {
+                    ip: 2,
+                    opcode: program[2] as i128,
+                    parameter: Some(0),
+                    reason,
+                }),
+                "{:?} with the instruction cache {}",
+                backend,
+                if cache_instructions { "on" } else { "off" }
+            );
+            assert_eq!(computer.ip(), 2);
+        }
+    }
+}
+
+#[test]
+fn test_puzzle_examples() {
+    let quine = [
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    check(&quine, &[], |computer| {
+        assert_eq!(computer.all_outputs(), &quine);
+    });
+
+    check(
+        &[1102, 34915192, 34915192, 7, 4, 7, 99, 0],
+        &[],
+        |computer| {
+            assert_eq!(computer.all_outputs(), &[1219070632396864]);
+        },
+    );
+
+    check(&[104, 1125899906842624, 99], &[], |computer| {
+        assert_eq!(computer.all_outputs(), &[1125899906842624]);
+    });
+
+    // Outputs 999 below 8, 1000 for 8 and 1001 above it
+    let compare_to_8 = [
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
+        1105, 1, 46, 98, 99,
+    ];
+    for &(input, output) in &[(-5, 999), (7, 999), (8, 1000), (9, 1001)] {
+        check(&compare_to_8, &[input], |computer| {
+            assert_eq!(computer.all_outputs(), &[output]);
+        });
+    }
+
+    // Equal to 8 and less than 8, in position and immediate mode
+    type Expected = fn(isize) -> bool;
+
+    let comparisons: [(&[isize], Expected); 4] = [
+        (&[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8], |input| input == 8),
+        (&[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8], |input| input < 8),
+        (&[3, 3, 1108, -1, 8, 3, 4, 3, 99], |input| input == 8),
+        (&[3, 3, 1107, -1, 8, 3, 4, 3, 99], |input| input < 8),
+    ];
+    for &(program, expected) in &comparisons {
+        for &input in &[7, 8, 9] {
+            check(program, &[input], |computer| {
+                assert_eq!(computer.all_outputs(), &[expected(input) as isize]);
+            });
+        }
+    }
+
+    // Output 0 for an input of 0 and 1 otherwise, with position and immediate mode jumps
+    for program in &[
+        &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9][..],
+        &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1][..],
+    ] {
+        for &input in &[0, 5] {
+            check(program, &[input], |computer| {
+                assert_eq!(computer.all_outputs(), &[(input != 0) as isize]);
+            });
+        }
+    }
+}
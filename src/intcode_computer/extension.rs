@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use super::{Computer, IntcodeError, Memory, PagedMemory, Parameter};
+
+/// How an extension instruction uses one of its parameters.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    /// The parameter is a value, in any mode.
+    Read,
+    /// The parameter is an address that's written to, immediate mode is rejected when decoding.
+    Write,
+}
+
+/// What the computer does once an extension instruction was executed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Effect {
+    /// Continue with the next instruction.
+    Continue,
+    /// Continue at the address.
+    Jump(usize),
+    /// Continue with the next instruction, but return
+    /// [`ExecutionState::Yielded`](super::ExecutionState::Yielded) from the current run first.
+    Yield,
+    /// Halt without advancing the instruction pointer, like the halt instruction.
+    Halt,
+}
+
+/// The parameters of an extension instruction while its handler runs.
+pub struct Operands<'a, M: Memory> {
+    computer: &'a mut Computer<M>,
+    parameters: &'a [Parameter<M::Word>],
+}
+
+impl<'a, M: Memory> Operands<'a, M> {
+    /// The value of parameter `index`, which must be a [`Access::Read`] parameter.
+    pub fn read(&self, index: usize) -> Result<M::Word, IntcodeError> {
+        self.computer
+            .value(&self.parameters[index])
+            .map_err(|reason| self.computer.fault(index, reason))
+    }
+
+    /// Write `value` to the address of parameter `index`, which must be a [`Access::Write`]
+    /// parameter.
+    pub fn write(&mut self, index: usize, value: M::Word) -> Result<(), IntcodeError> {
+        let written = self.parameters[index]
+            .address(self.computer.relative_base_offset)
+            .and_then(|address| self.computer.write::<true>(address, value));
+
+        written.map_err(|reason| self.computer.fault(index, reason))
+    }
+
+    /// The computer executing the instruction, e.g. to queue inputs or take outputs.
+    pub fn computer(&mut self) -> &mut Computer<M> {
+        self.computer
+    }
+}
+
+type Handler<M> =
+    Arc<dyn Fn(&mut Operands<'_, M>) -> Result<Effect, IntcodeError> + Send + Sync + 'static>;
+
+struct Extension<M: Memory> {
+    mnemonic: &'static str,
+    access: Vec<Access>,
+    handler: Handler<M>,
+}
+
+impl<M: Memory> Clone for Extension<M> {
+    fn clone(&self) -> Self {
+        Self {
+            mnemonic: self.mnemonic,
+            access: self.access.clone(),
+            handler: Arc::clone(&self.handler),
+        }
+    }
+}
+
+/// Opcodes added to the standard instruction set, see [`Computer::set_extensions`].
+///
+/// Extension opcodes are the lowest two digits of an instruction word like standard opcodes, and
+/// their parameters use the same modes.
+pub struct Extensions<M: Memory = PagedMemory> {
+    /// Registered extensions, indexed by opcode.
+    opcodes: Vec<Option<Extension<M>>>,
+}
+
+impl<M: Memory> Default for Extensions<M> {
+    fn default() -> Self {
+        Self {
+            opcodes: vec![None; 100],
+        }
+    }
+}
+
+impl<M: Memory> Clone for Extensions<M> {
+    fn clone(&self) -> Self {
+        Self {
+            opcodes: self.opcodes.clone(),
+        }
+    }
+}
+
+/// Opcodes of the standard instruction set, which can't be replaced by extensions.
+const STANDARD_OPCODES: [usize; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+impl<M: Memory> Extensions<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Execute `handler` for instructions with `opcode`, whose parameters are used as listed in
+    /// `access`.
+    ///
+    /// Panics if `opcode` is a standard opcode, already registered or above 99, or if the
+    /// instruction has more than three parameters.
+    pub fn register<F>(
+        &mut self,
+        opcode: usize,
+        mnemonic: &'static str,
+        access: &[Access],
+        handler: F,
+    ) where
+        F: Fn(&mut Operands<'_, M>) -> Result<Effect, IntcodeError> + Send + Sync + 'static,
+    {
+        assert!(
+            !STANDARD_OPCODES.contains(&opcode),
+            "Opcode {} is a standard opcode",
+            opcode
+        );
+        assert!(opcode < 100, "Opcode {} has more than two digits", opcode);
+        assert!(
+            self.opcodes[opcode].is_none(),
+            "Opcode {} is already registered",
+            opcode
+        );
+        assert!(
+            access.len() <= 3,
+            "Instructions can have at most three parameters"
+        );
+
+        self.opcodes[opcode] = Some(Extension {
+            mnemonic,
+            access: access.to_vec(),
+            handler: Arc::new(handler),
+        });
+    }
+
+    /// The mnemonic and parameters of the extension registered for `opcode`.
+    pub fn signature(&self, opcode: usize) -> Option<(&'static str, &[Access])> {
+        let extension = self.opcodes.get(opcode)?.as_ref()?;
+
+        Some((extension.mnemonic, &extension.access))
+    }
+
+    pub(crate) fn execute(
+        &self,
+        opcode: usize,
+        computer: &mut Computer<M>,
+        parameters: &[Parameter<M::Word>],
+    ) -> Result<Effect, IntcodeError> {
+        let extension = self.opcodes[opcode]
+            .as_ref()
+            .expect("Only registered extensions are decoded");
+
+        (extension.handler)(&mut Operands {
+            computer,
+            parameters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Access, Effect, Extensions};
+    use crate::intcode_computer::{Computer, ExecutionState, IntcodeErrorReason};
+
+    fn max() -> Arc<Extensions> {
+        let mut extensions: Extensions = Extensions::new();
+        extensions.register(
+            20,
+            "max",
+            &[Access::Read, Access::Read, Access::Write],
+            |operands| {
+                let value = operands.read(0)?.max(operands.read(1)?);
+                operands.write(2, value)?;
+
+                Ok(Effect::Continue)
+            },
+        );
+
+        Arc::new(extensions)
+    }
+
+    #[test]
+    fn test_registered_opcode() {
+        // Outputs the larger of its input and 5
+        let program = vec![3, 9, 1020, 9, 5, 10, 4, 10, 99, 0, 0];
+
+        for &(input, output) in &[(3, 5), (8, 8)] {
+            let mut computer = Computer::new(program.clone());
+            computer.set_extensions(Some(max()));
+            computer.push_input(input);
+
+            assert_eq!(computer.step(), Ok(None));
+            assert_eq!(
+                computer.current_instruction().map(|i| i.to_string()),
+                Ok("max [9], #5, [10]".to_string())
+            );
+            assert_eq!(computer.try_run(false), Ok(ExecutionState::Halted));
+            assert_eq!(computer.all_outputs(), &[output]);
+        }
+
+        let mut computer = Computer::new(program.clone());
+        computer.push_input(3);
+        assert_eq!(
+            computer.try_run(false).unwrap_err().reason,
+            IntcodeErrorReason::InvalidOpcode
+        );
+
+        let mut computer = Computer::new(vec![11020, 9, 5, 10, 99]);
+        computer.set_extensions(Some(max()));
+        assert_eq!(
+            computer.try_run(false).unwrap_err().reason,
+            IntcodeErrorReason::ImmediateWrite
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Opcode 9 is a standard opcode")]
+    fn test_standard_opcodes_cant_be_replaced() {
+        let mut extensions: Extensions = Extensions::new();
+
+        extensions.register(9, "arb", &[Access::Read], |_| Ok(Effect::Continue));
+    }
+}
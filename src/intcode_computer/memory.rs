@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use super::{IntcodeErrorReason, Word};
+
+/// Storage for the words of an Intcode program.
+///
+/// Memory is unbounded, every address that was never written reads as 0.
+pub trait Memory: 'static {
+    type Word: Word;
+
+    fn read(&self, address: usize) -> Self::Word;
+
+    /// Write a word, failing if storing it would need more memory than the implementation allows.
+    fn write(&mut self, address: usize, value: Self::Word) -> Result<(), IntcodeErrorReason>;
+
+    /// Every allocated run of words with the address of its first word, ordered by address.
+    ///
+    /// Words outside of the returned runs are zero.
+    fn segments(&self) -> Vec<(usize, &[Self::Word])>;
+
+    /// Reset every word to zero.
+    fn clear(&mut self);
+}
+
+/// Number of words in a page, 4 KiB worth of `isize` words on 64 bit targets.
+pub const PAGE_SIZE: usize = 512;
+/// Pages below this index are kept in a directory indexed by page, pages above it in a map.
+const DENSE_PAGES: usize = 4096;
+
+type Page<W> = Box<[W; PAGE_SIZE]>;
+
+/// Sparse memory that allocates fixed size pages as they are first written.
+///
+/// Reading never allocates so a program can access addresses as large as `isize::MAX` while
+/// only paying for the pages it writes to. An optional limit, in words, turns writes that would
+/// allocate past it into [`IntcodeErrorReason::MemoryLimitExceeded`].
+#[derive(Clone, Default)]
+pub struct PagedMemory<W: Word = isize> {
+    dense: Vec<Option<Page<W>>>,
+    sparse: HashMap<usize, Page<W>>,
+    allocated_pages: usize,
+    limit: Option<usize>,
+}
+
+impl<W: Word> PagedMemory<W> {
+    pub fn new(program: &[W]) -> Self {
+        let mut memory = Self::default();
+        memory.load(program);
+
+        memory
+    }
+
+    /// Write `program` starting at address 0, ignoring the limit.
+    fn load(&mut self, program: &[W]) {
+        for (page, words) in program.chunks(PAGE_SIZE).enumerate() {
+            self.page_mut(page)[..words.len()].copy_from_slice(words);
+        }
+    }
+
+    /// Limit the memory to `limit` words, rounded down to whole pages.
+    ///
+    /// Pages that are already allocated are kept even if they exceed the new limit.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Number of words currently allocated.
+    pub fn allocated(&self) -> usize {
+        self.allocated_pages * PAGE_SIZE
+    }
+
+    #[inline(always)]
+    fn page(&self, page: usize) -> Option<&Page<W>> {
+        if page < DENSE_PAGES {
+            self.dense.get(page).and_then(Option::as_ref)
+        } else {
+            self.sparse.get(&page)
+        }
+    }
+
+    fn page_mut(&mut self, page: usize) -> &mut Page<W> {
+        let slot = if page < DENSE_PAGES {
+            if page >= self.dense.len() {
+                self.dense.resize_with(page + 1, Default::default);
+            }
+
+            &mut self.dense[page]
+        } else {
+            let allocated_pages = &mut self.allocated_pages;
+
+            return self.sparse.entry(page).or_insert_with(|| {
+                *allocated_pages += 1;
+                Box::new([W::ZERO; PAGE_SIZE])
+            });
+        };
+
+        if slot.is_none() {
+            *slot = Some(Box::new([W::ZERO; PAGE_SIZE]));
+            self.allocated_pages += 1;
+        }
+
+        slot.as_mut().unwrap()
+    }
+}
+
+impl<W: Word> Memory for PagedMemory<W> {
+    type Word = W;
+
+    #[inline(always)]
+    fn read(&self, address: usize) -> W {
+        self.page(address / PAGE_SIZE)
+            .map_or(W::ZERO, |page| page[address % PAGE_SIZE])
+    }
+
+    #[inline(always)]
+    fn write(&mut self, address: usize, value: W) -> Result<(), IntcodeErrorReason> {
+        let page = address / PAGE_SIZE;
+
+        if self.page(page).is_none() {
+            let exceeds_limit = self
+                .limit
+                .is_some_and(|limit| self.allocated() + PAGE_SIZE > limit);
+
+            if exceeds_limit {
+                return Err(IntcodeErrorReason::MemoryLimitExceeded(address));
+            }
+        }
+
+        self.page_mut(page)[address % PAGE_SIZE] = value;
+
+        Ok(())
+    }
+
+    fn segments(&self) -> Vec<(usize, &[W])> {
+        let dense = self
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(page, words)| words.as_ref().map(|words| (page, words)));
+        let mut sparse: Vec<_> = self
+            .sparse
+            .iter()
+            .map(|(&page, words)| (page, words))
+            .collect();
+        sparse.sort_by_key(|&(page, _)| page);
+
+        dense
+            .chain(sparse)
+            .map(|(page, words)| (page * PAGE_SIZE, &words[..]))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.dense.clear();
+        self.sparse.clear();
+        self.allocated_pages = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Memory, PagedMemory, PAGE_SIZE};
+    use crate::intcode_computer::IntcodeErrorReason;
+
+    #[test]
+    fn test_sparse_addresses() {
+        let mut memory: PagedMemory = PagedMemory::new(&[1, 2, 3]);
+        let high = usize::MAX / 2;
+
+        assert_eq!(memory.read(high), 0);
+        assert_eq!(memory.allocated(), PAGE_SIZE);
+
+        memory.write(high, 42).unwrap();
+        memory.write(high - PAGE_SIZE, 7).unwrap();
+        assert_eq!(memory.read(high), 42);
+        assert_eq!(memory.read(high - 1), 0);
+        assert_eq!(memory.read(2), 3);
+        assert_eq!(memory.allocated(), 3 * PAGE_SIZE);
+
+        let segments: Vec<_> = memory
+            .segments()
+            .into_iter()
+            .map(|(address, words)| (address, words.len()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (0, PAGE_SIZE),
+                (high - high % PAGE_SIZE - PAGE_SIZE, PAGE_SIZE),
+                (high - high % PAGE_SIZE, PAGE_SIZE)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut memory: PagedMemory = PagedMemory::new(&[1, 2, 3]);
+        memory.set_limit(Some(2 * PAGE_SIZE));
+
+        memory.write(PAGE_SIZE - 1, 1).unwrap();
+        memory.write(5 * PAGE_SIZE, 2).unwrap();
+        assert_eq!(
+            memory.write(usize::MAX / 2, 3),
+            Err(IntcodeErrorReason::MemoryLimitExceeded(usize::MAX / 2))
+        );
+        assert_eq!(memory.read(usize::MAX / 2), 0);
+
+        memory.clear();
+        memory.write(usize::MAX / 2, 3).unwrap();
+        assert_eq!(memory.read(0), 0);
+    }
+}
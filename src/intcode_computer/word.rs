@@ -0,0 +1,74 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+/// An integer type that Intcode programs compute with, chosen by the [`Memory`](super::Memory)
+/// of a [`Computer`](super::Computer).
+///
+/// Implemented for `isize`, which every computer uses by default, `i64` and `i128`. Words are
+/// only ever converted to `isize` when they're used as an address or a relative base adjustment.
+///
+/// Words are `Copy` and at most 128 bits wide, which the memories and the compiled backend rely
+/// on, so arbitrary precision integers are out of scope. Programs that outgrow `i128` should be
+/// run with [`Overflow::Trap`](super::Overflow::Trap) to find out.
+pub trait Word:
+    Copy + Default + Ord + Hash + fmt::Debug + fmt::Display + FromStr + Send + Sync + 'static
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    /// The word as an `isize`, or `None` if it doesn't fit.
+    fn to_isize(self) -> Option<isize>;
+
+    /// The word as an `i128`, for reporting faults. Lossless, as no word is wider than `i128`.
+    fn to_i128(self) -> i128;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+}
+
+macro_rules! impl_word {
+    ($($word:ty),*) => {
+        $(
+            impl Word for $word {
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+
+                #[inline(always)]
+                fn to_isize(self) -> Option<isize> {
+                    isize::try_from(self).ok()
+                }
+
+                #[inline(always)]
+                fn to_i128(self) -> i128 {
+                    self as i128
+                }
+
+                #[inline(always)]
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$word>::checked_add(self, other)
+                }
+
+                #[inline(always)]
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$word>::checked_mul(self, other)
+                }
+
+                #[inline(always)]
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$word>::wrapping_add(self, other)
+                }
+
+                #[inline(always)]
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$word>::wrapping_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(isize, i64, i128);
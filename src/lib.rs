@@ -28,6 +28,7 @@ mod day21;
 mod day22;
 mod day23;
 mod day24;
+pub mod intcode;
 pub mod intcode_computer;
 mod math;
 
@@ -344,6 +345,29 @@ mod tests {
         assert_eq!(star_two(&input), 15973);
     }
 
+    #[test]
+    fn replay_day13() {
+        use crate::day13::free_play;
+        use crate::intcode::patch::PatchSet;
+        use crate::intcode::program::Program;
+        use crate::intcode::record::{replay, Recording};
+        use crate::intcode_computer::Computer;
+
+        let patches: PatchSet = load_file("day13_free_play.txt").parse().unwrap();
+        assert_eq!(patches, free_play());
+
+        let program = Program::from_file("day13.txt").unwrap().patched(&patches);
+        let recording: Recording = load_file("day13_session.txt").parse().unwrap();
+
+        assert_eq!(
+            replay(&mut Computer::new(program.words()), &recording),
+            Ok(())
+        );
+        // The session is the first hundred moves of a game
+        assert!(!recording.halted);
+        assert_eq!(recording.inputs().count(), 100);
+    }
+
     #[test]
     fn solve_day14() {
         use crate::day14::{star_one, star_two};
@@ -364,6 +388,21 @@ mod tests {
         assert_eq!(star_two(&input), 312);
     }
 
+    #[test]
+    fn replay_day15() {
+        use crate::intcode::program::Program;
+        use crate::intcode::record::{replay, Recording};
+        use crate::intcode_computer::Computer;
+
+        let program = Program::from_file("day15.txt").unwrap();
+        let recording: Recording = load_file("day15_session.txt").parse().unwrap();
+
+        assert_eq!(
+            replay(&mut Computer::new(program.words()), &recording),
+            Ok(())
+        );
+    }
+
     #[test]
     fn solve_day16() {
         use crate::day16::{star_one, star_two};